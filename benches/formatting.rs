@@ -0,0 +1,132 @@
+//! Measures how long it takes to log a line in each format, and how many
+//! allocations that takes. Logging should not allocate once the per-thread
+//! buffer has grown to fit a line. Fields bound to a child logger cost a single
+//! allocation per line, no matter how many there are.
+//!
+//! Run with `cargo bench --bench formatting`.
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  hint::black_box,
+  io,
+  sync::atomic::{AtomicUsize, Ordering},
+  time::Instant,
+};
+
+use lumbermill::{
+  fields, format::Template, info, Log, LogFormat, Logger, Sink,
+};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn realloc(
+    &self,
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+  ) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.realloc(ptr, layout, new_size)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Throws formatted lines away, so only formatting is measured.
+struct Discard;
+
+impl Sink for Discard {
+  fn log(&self, _: &Log, formatted: &[u8]) -> io::Result<()> {
+    black_box(formatted);
+    Ok(())
+  }
+}
+
+const ITERATIONS: u32 = 100_000;
+
+fn main() {
+  let formats = [
+    ("pretty", LogFormat::Pretty),
+    ("pretty_structured", LogFormat::PrettyStructured),
+    ("compact", LogFormat::Compact),
+    ("logfmt", LogFormat::Logfmt),
+    ("json", LogFormat::Json),
+    (
+      "template",
+      LogFormat::Template(
+        Template::new("{ts} [{level:>5}] {module}: {message} {kv}").unwrap(),
+      ),
+    ),
+  ];
+
+  for (name, format) in formats {
+    let logger = Logger::new().stdout(false).format(format).sink(Discard);
+
+    // Warm up, so the per-thread buffer has grown to fit a line
+    for i in 0..100 {
+      info!(logger: &logger, request_id = i, path = "/health", "Handled in {}ms", 12);
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+      info!(logger: &logger, request_id = i, path = "/health", "Handled in {}ms", 12);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+      "{name:<18} {:>8.1} ns/line {:>6.2} allocations/line",
+      elapsed.as_nanos() as f64 / f64::from(ITERATIONS),
+      allocations as f64 / f64::from(ITERATIONS),
+    );
+    assert_eq!(allocations, 0, "Logging in the {name} format allocated");
+  }
+
+  let logger = Logger::new().stdout(false).logfmt().sink(Discard);
+  let child = logger.child(fields!(
+    service = "api",
+    region = "fra",
+    node = 1,
+    shard = 3,
+    component = "db",
+    pool = "primary",
+    tenant = "acme",
+    version = "1.2.3",
+  ));
+  for i in 0..100 {
+    info!(logger: child, request_id = i, "Handled in {}ms", 12);
+  }
+
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+  let start = Instant::now();
+  for i in 0..ITERATIONS {
+    info!(logger: child, request_id = i, "Handled in {}ms", 12);
+  }
+  let elapsed = start.elapsed();
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+  println!(
+    "{:<18} {:>8.1} ns/line {:>6.2} allocations/line",
+    "child (8 fields)",
+    elapsed.as_nanos() as f64 / f64::from(ITERATIONS),
+    allocations as f64 / f64::from(ITERATIONS),
+  );
+  assert_eq!(
+    allocations, ITERATIONS as usize,
+    "Bound fields allocated more than once"
+  );
+}
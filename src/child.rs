@@ -0,0 +1,104 @@
+use crate::{fields::with_fields, with_current_logger, Fields, Log, Logger};
+
+/// A lightweight logging handle with key-value pairs bound to it.
+///
+/// Bound pairs are prepended to the key-value pairs of every line logged
+/// through this handle. Create one with the [`child!`](crate::child) macro, and
+/// log through it by passing it to any of the logging macros.
+///
+/// Children created with `child!(...)` log wherever the logging macros do, see
+/// [`with_current_logger`]. Children created with `child!(logger: &my_logger,
+/// ...)` (or [`Logger::child`]) log to `my_logger` instead.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{child, info, Logger};
+///
+/// Logger::default().init();
+///
+/// let db_log = child!(component = "db", shard = 3);
+/// info!(logger: db_log, "Connected");
+///
+/// // Children of children keep their parent's pairs
+/// let query_log = child!(logger: db_log, table = "users");
+/// info!(logger: query_log, rows = 9001, "Query finished");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChildLogger<'a> {
+  logger: Option<&'a Logger>,
+  fields: Fields,
+}
+
+impl<'a> ChildLogger<'a> {
+  /// Create a handle that logs to the global [`LOGGER`](crate::LOGGER).
+  pub fn new(fields: Fields) -> Self {
+    Self {
+      logger: None,
+      fields,
+    }
+  }
+
+  pub(crate) fn with_logger(logger: &'a Logger, fields: Fields) -> Self {
+    Self {
+      logger: Some(logger),
+      fields,
+    }
+  }
+
+  /// Create a new handle with `fields` bound in addition to this handle's own.
+  pub fn child(&self, fields: Fields) -> Self {
+    let mut child = self.clone();
+    child.fields.extend(fields);
+    child
+  }
+
+  pub fn fields(&self) -> &Fields {
+    &self.fields
+  }
+
+  pub fn log(&self, log: Log) {
+    match self.logger {
+      Some(logger) => self.log_to(logger, log),
+      None => _ = with_current_logger(|logger| self.log_to(logger, log)),
+    }
+  }
+
+  fn log_to(&self, logger: &Logger, log: Log) {
+    if !logger.enabled(log.level) {
+      return;
+    }
+
+    with_fields(&self.fields, log.kv, |kv| logger.log(Log { kv, ..log }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{assert_logged, child, fields, info, testing::capture, LogLevel};
+
+  #[test]
+  fn child_of_child() {
+    let db_log = child!(component = "db", shard = 3);
+    let query_log = child!(logger: db_log, table = "users");
+
+    assert_eq!(
+      query_log.fields(),
+      &fields!(component = "db", shard = 3, table = "users")
+    );
+    assert_eq!(db_log.fields(), &fields!(component = "db", shard = 3));
+
+    capture(|| {
+      info!(logger: db_log, "Connected");
+      info!(logger: query_log, rows = 9001, "Query finished");
+
+      assert_logged!(LogLevel::Info, component = "db", message = "Connected");
+      assert_logged!(
+        LogLevel::Info,
+        shard = 3,
+        table = "users",
+        rows = 9001,
+        message = "Query finished"
+      );
+    });
+  }
+}
@@ -0,0 +1,103 @@
+use std::future::Future;
+
+use tokio::task::futures::TaskLocalFuture;
+
+use crate::Fields;
+
+tokio::task_local! {
+  static CONTEXT: Fields;
+}
+
+/// Attach key-value pairs to every log line emitted while a future runs.
+///
+/// Context is stored in a tokio task-local, so it follows the future across
+/// `.await` points, even when it migrates between worker threads. Contexts
+/// created inside another context extend its fields. Tasks spawned from within
+/// the future do not inherit its context, wrap them again if they need it.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{fields, info, WithLogContext};
+///
+/// async fn handle(request_id: u64) {
+///   async {
+///     // Logged with `request_id` attached
+///     info!("Handling request");
+///   }
+///   .with_log_context(fields!(request_id))
+///   .await
+/// }
+/// ```
+pub trait WithLogContext: Future + Sized {
+  fn with_log_context(self, fields: Fields) -> TaskLocalFuture<Fields, Self> {
+    let mut context = CONTEXT.try_with(Fields::clone).unwrap_or_default();
+    context.extend(fields);
+    CONTEXT.scope(context, self)
+  }
+}
+
+impl<F: Future> WithLogContext for F {}
+
+/// Calls `f` with the context of the current task, if there is one.
+pub(crate) fn with_current<R>(f: impl FnOnce(&Fields) -> R) -> Option<R> {
+  CONTEXT.try_with(f).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{assert_logged, fields, info, testing::capture, LogLevel};
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+  async fn survives_await_points() {
+    let context = async {
+      for _ in 0..10 {
+        tokio::task::yield_now().await;
+      }
+      with_current(Fields::clone)
+    }
+    .with_log_context(fields!(request_id = 42))
+    .await;
+
+    assert_eq!(context, Some(fields!(request_id = 42)));
+  }
+
+  #[tokio::test]
+  async fn nested() {
+    let context = async {
+      async { with_current(Fields::clone) }
+        .with_log_context(fields!(user = "toph"))
+        .await
+    }
+    .with_log_context(fields!(request_id = 42))
+    .await;
+
+    assert_eq!(context, Some(fields!(request_id = 42, user = "toph")));
+  }
+
+  #[test]
+  fn attached_to_logs() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+      .build()
+      .unwrap();
+
+    capture(|| {
+      runtime.block_on(
+        async { info!(path = "/health", "Handling request") }
+          .with_log_context(fields!(request_id = 42)),
+      );
+
+      assert_logged!(
+        LogLevel::Info,
+        request_id = 42,
+        path = "/health",
+        message = "Handling request"
+      );
+    });
+  }
+
+  #[test]
+  fn outside_of_a_task() {
+    assert_eq!(with_current(Fields::clone), None);
+  }
+}
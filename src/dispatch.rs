@@ -0,0 +1,164 @@
+use std::{
+  cell::Cell,
+  thread::{self, ScopedJoinHandle},
+};
+
+use crate::{Logger, LOGGER};
+
+thread_local! {
+  static OVERRIDE: Cell<Option<*const Logger>> = const { Cell::new(None) };
+}
+
+/// Log to `logger` instead of the global [`LOGGER`] from the current thread
+/// while `f` runs.
+///
+/// This is mostly useful in tests, where the global logger can only be set once
+/// for the whole test binary. Threads spawned with [`scope`] inherit the
+/// override, other threads do not.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{info, with_logger, Logger};
+///
+/// let logger = Logger::default().json();
+/// with_logger(&logger, || {
+///   info!("Logged as JSON");
+/// });
+/// ```
+pub fn with_logger<R>(logger: &Logger, f: impl FnOnce() -> R) -> R {
+  struct Restore(Option<*const Logger>);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      OVERRIDE.with(|o| o.set(self.0));
+    }
+  }
+
+  let _restore = Restore(OVERRIDE.with(|o| o.replace(Some(logger))));
+  f()
+}
+
+/// Calls `f` with the logger the logging macros currently log to: the one set
+/// by [`with_logger`] if there is one, the global [`LOGGER`] otherwise.
+///
+/// Returns `None` if there is no logger to log to.
+pub fn with_current_logger<R>(f: impl FnOnce(&Logger) -> R) -> Option<R> {
+  match OVERRIDE.with(Cell::get) {
+    // SAFETY: `with_logger` removes the override before the `Logger` it points
+    // to can go out of scope, and `f` cannot hold on to the reference.
+    Some(logger) => Some(f(unsafe { &*logger })),
+    None => LOGGER.get().map(f),
+  }
+}
+
+/// A scope to spawn threads in, see [`scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+  scope: &'scope thread::Scope<'scope, 'env>,
+  logger: Option<&'scope Logger>,
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+  /// Spawn a scoped thread that logs to the same logger as the thread that
+  /// created the scope. See [`std::thread::Scope::spawn`].
+  pub fn spawn<F, T>(&self, f: F) -> ScopedJoinHandle<'scope, T>
+  where
+    F: FnOnce() -> T + Send + 'scope,
+    T: Send + 'scope,
+  {
+    let logger = self.logger;
+    self.scope.spawn(move || match logger {
+      Some(logger) => with_logger(logger, f),
+      None => f(),
+    })
+  }
+}
+
+/// Like [`std::thread::scope`], except spawned threads inherit the logger set
+/// by [`with_logger`] on the current thread.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{info, with_logger, Logger};
+///
+/// let logger = Logger::default().compact();
+/// with_logger(&logger, || {
+///   lumbermill::scope(|s| {
+///     s.spawn(|| info!("Logged with the compact format"));
+///   });
+/// });
+/// ```
+pub fn scope<'env, F, T>(f: F) -> T
+where
+  F: for<'scope> FnOnce(&Scope<'scope, 'env>) -> T,
+{
+  let logger = OVERRIDE.with(Cell::get);
+  thread::scope(|scope| {
+    // SAFETY: The override outlives this function call, and all threads
+    // spawned in the scope are joined before it returns.
+    let logger = logger.map(|logger| unsafe { &*logger });
+    f(&Scope { scope, logger })
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use super::*;
+  use crate::{info, RollInterval};
+
+  fn file_logger(dir: &PathBuf) -> Logger {
+    fs::create_dir_all(dir).unwrap();
+    Logger::new()
+      .stdout(false)
+      .compact()
+      .file(dir, RollInterval::None)
+  }
+
+  fn read_logs(dir: &PathBuf) -> Vec<String> {
+    let logs = fs::read_to_string(dir.join("log.log")).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    logs.lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn overrides_the_global_logger() {
+    let dir = std::env::temp_dir()
+      .join(format!("lumbermill-override-{}", std::process::id()));
+    let logger = file_logger(&dir);
+
+    with_logger(&logger, || {
+      info!("Overridden");
+
+      let inner = Logger::new().stdout(false);
+      with_logger(&inner, || info!("Nested"));
+
+      info!("Restored");
+    });
+    info!("Global");
+    drop(logger);
+
+    let logs = read_logs(&dir);
+    assert_eq!(logs.len(), 2);
+    assert!(logs[0].contains("message=\"Overridden\""));
+    assert!(logs[1].contains("message=\"Restored\""));
+  }
+
+  #[test]
+  fn scoped_threads_inherit_the_override() {
+    let dir = std::env::temp_dir()
+      .join(format!("lumbermill-scoped-{}", std::process::id()));
+    let logger = file_logger(&dir);
+
+    with_logger(&logger, || {
+      scope(|s| {
+        s.spawn(|| info!("Spawned"));
+      })
+    });
+    drop(logger);
+
+    let logs = read_logs(&dir);
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("message=\"Spawned\""));
+  }
+}
@@ -0,0 +1,207 @@
+use std::fmt::{Arguments, Display};
+
+/// An owned list of key-value pairs.
+///
+/// Unlike the pairs attached to a single [`Log`](crate::Log), `Fields` own their
+/// values, so they can be stored and attached to many log lines later on. The
+/// easiest way to build one is the [`fields!`](crate::fields) macro.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(Vec<(&'static str, String)>);
+
+impl Fields {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn push<V: Display>(&mut self, key: &'static str, value: V) {
+    self.0.push((key, value.to_string()));
+  }
+
+  pub fn extend(&mut self, other: Fields) {
+    self.0.extend(other.0);
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> {
+    self.0.iter().map(|(k, v)| (*k, v.as_str()))
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+impl From<Vec<(&'static str, String)>> for Fields {
+  fn from(fields: Vec<(&'static str, String)>) -> Self {
+    Self(fields)
+  }
+}
+
+/// How many bound fields are turned into `Arguments` per stack frame.
+const CHUNK: usize = 16;
+
+/// Past this many bound fields, the rest are rendered into a single `fields`
+/// value, so that huge lists cannot overflow the stack.
+const MAX_RENDERED: usize = 64 * CHUNK;
+
+/// Up to `CHUNK` fields rendered to `Arguments`, and the ones rendered before
+/// them.
+struct Rendered<'a> {
+  pairs: [(&'static str, Arguments<'a>); CHUNK],
+  len: usize,
+  prev: Option<&'a Rendered<'a>>,
+}
+
+/// Calls `f` with `fields` prepended to `kv`.
+///
+/// `Arguments` can only borrow from temporaries that live until the end of the
+/// enclosing statement, so fields are turned into `Arguments` a chunk at a
+/// time, one recursive call per chunk, and chained to the previous chunk on
+/// the stack. The innermost call copies the chain and `kv` into a single list,
+/// which is the only allocation.
+pub(crate) fn with_fields<R>(
+  fields: &Fields,
+  kv: &[(&'static str, Arguments)],
+  f: impl FnOnce(&[(&'static str, Arguments)]) -> R,
+) -> R {
+  fn render<R>(
+    fields: &[(&'static str, String)],
+    rendered: Option<&Rendered>,
+    len: usize,
+    kv: &[(&'static str, Arguments)],
+    f: impl FnOnce(&[(&'static str, Arguments)]) -> R,
+  ) -> R {
+    if fields.is_empty() {
+      let mut all = Vec::with_capacity(len + kv.len());
+      let mut next = rendered;
+      while let Some(r) = next {
+        all.extend(r.pairs[..r.len].iter().rev());
+        next = r.prev;
+      }
+      all.reverse();
+      all.extend_from_slice(kv);
+      return f(&all);
+    }
+
+    let (chunk, rest) = fields.split_at(fields.len().min(CHUNK));
+    // Short chunks repeat their last field, which is then ignored
+    let field = |i: usize| &chunk[i.min(chunk.len() - 1)];
+    macro_rules! pairs {
+      ($($i:literal)*) => {
+        [$((field($i).0, format_args!("{}", field($i).1))),*]
+      };
+    }
+
+    render(
+      rest,
+      Some(&Rendered {
+        pairs: pairs!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15),
+        len: chunk.len(),
+        prev: rendered,
+      }),
+      len,
+      kv,
+      f,
+    )
+  }
+
+  if fields.is_empty() {
+    return f(kv);
+  }
+
+  if fields.len() > MAX_RENDERED {
+    // Rare enough that copying the fields does not matter
+    let (head, rest) = fields.0.split_at(MAX_RENDERED - 1);
+    let mut overflow = String::new();
+    for (i, (k, v)) in rest.iter().enumerate() {
+      if i > 0 {
+        overflow.push(' ');
+      }
+      overflow.push_str(k);
+      overflow.push('=');
+      overflow.push_str(v);
+    }
+
+    let mut owned = head.to_vec();
+    owned.push(("fields", overflow));
+    return render(&owned, None, owned.len(), kv, f);
+  }
+
+  render(&fields.0, None, fields.len(), kv, f)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn prepends_fields() {
+    let fields = crate::fields!(service = "toph", node = "fra");
+    let kv =
+      with_fields(&fields, &[("message", format_args!("Listening"))], |kv| {
+        kv.iter()
+          .map(|(k, v)| format!("{k}={v}"))
+          .collect::<Vec<_>>()
+      });
+
+    assert_eq!(kv, ["service=toph", "node=fra", "message=Listening"]);
+  }
+
+  #[test]
+  fn many_fields() {
+    let mut fields = Fields::new();
+    for i in 0..40 {
+      fields.push("i", i);
+    }
+    let kv = with_fields(&fields, &[("message", format_args!("Hi"))], |kv| {
+      kv.iter().map(|(_, v)| v.to_string()).collect::<Vec<_>>()
+    });
+    let expected: Vec<_> = (0..40).map(|i| i.to_string()).collect();
+    assert_eq!(kv[..40], expected);
+    assert_eq!(kv[40], "Hi");
+
+    // Huge lists do not overflow a small stack
+    let mut fields = Fields::new();
+    for i in 0..100_000 {
+      fields.push("i", i);
+    }
+    let kv = std::thread::Builder::new()
+      .stack_size(256 * 1024)
+      .spawn(move || {
+        with_fields(&fields, &[], |kv| {
+          kv.iter()
+            .map(|(k, v)| (*k, v.to_string()))
+            .collect::<Vec<_>>()
+        })
+      })
+      .unwrap()
+      .join()
+      .unwrap();
+    assert_eq!(kv.len(), MAX_RENDERED);
+    assert_eq!(kv[MAX_RENDERED - 2], ("i", (MAX_RENDERED - 2).to_string()));
+    assert_eq!(kv[MAX_RENDERED - 1].0, "fields");
+    assert!(kv[MAX_RENDERED - 1].1.starts_with("i=1023 i=1024 "));
+  }
+
+  #[test]
+  fn macro_syntax() {
+    let ip = "0.0.0.0";
+    let port = 7096;
+
+    let fields = crate::fields!(ip, ?port, addr.ip = ip, addr.port = ?port,);
+    assert_eq!(
+      fields.iter().collect::<Vec<_>>(),
+      [
+        ("ip", "0.0.0.0"),
+        ("port", "7096"),
+        ("addr.ip", "0.0.0.0"),
+        ("addr.port", "7096"),
+      ]
+    );
+
+    assert!(crate::fields!().is_empty());
+  }
+}
@@ -8,7 +8,7 @@ use std::{
 use parking_lot::Mutex;
 use time::{Duration, OffsetDateTime, Time};
 
-use crate::log::{Log, LogFormat};
+use crate::{log::Log, sink::Sink};
 
 #[derive(Debug)]
 pub enum RollInterval {
@@ -19,6 +19,11 @@ pub enum RollInterval {
   Daily,
 }
 
+/// A [`Sink`] that writes to log files in a directory, starting a new file
+/// every [`RollInterval`].
+///
+/// [`Logger::file`](crate::Logger::file) is the usual way to log to files, use
+/// this directly to wrap it in another sink, like [`NonBlocking`](crate::NonBlocking).
 #[derive(Debug)]
 pub struct FileLogger {
   directory: PathBuf,
@@ -44,8 +49,10 @@ impl FileLogger {
       roll_date: AtomicUsize::new(roll_date),
     }
   }
+}
 
-  pub fn log(&self, log: &Log, format: &LogFormat) -> io::Result<()> {
+impl Sink for FileLogger {
+  fn log(&self, log: &Log, formatted: &[u8]) -> io::Result<()> {
     let mut guard = self.file.lock();
     let file = guard.get_mut();
 
@@ -53,7 +60,7 @@ impl FileLogger {
     let now = log.timestamp;
 
     if roll_date == 0 {
-      return log.write(file, format);
+      return file.write_all(formatted);
     }
 
     if now.unix_timestamp() as usize > roll_date {
@@ -72,7 +79,7 @@ impl FileLogger {
       );
     }
 
-    log.write(file, format)
+    file.write_all(formatted)
   }
 }
 
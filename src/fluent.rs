@@ -0,0 +1,614 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::{
+  collections::hash_map::RandomState,
+  hash::{BuildHasher, Hasher},
+  io::{self, Read, Write},
+  net::TcpStream,
+  path::PathBuf,
+  sync::atomic::{AtomicU64, Ordering},
+  time::Duration,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+  log::Log,
+  msgpack::{
+    read_str_map, write_array_len, write_fixext8, write_map_len, write_str,
+    write_uint,
+  },
+  sink::{connect_any, Sink},
+  Record,
+};
+
+#[derive(Debug)]
+enum Transport {
+  Tcp(String),
+  #[cfg(unix)]
+  Unix(PathBuf),
+}
+
+#[derive(Debug)]
+enum Connection {
+  Tcp(TcpStream),
+  #[cfg(unix)]
+  Unix(UnixStream),
+}
+
+/// A [`Sink`] that sends records to Fluentd or Fluent Bit, using the
+/// [Forward protocol](https://github.com/fluent/fluentd/wiki/Forward-Protocol-Specification-v1).
+///
+/// Records are batched into Forward mode messages, one per tag, and each
+/// message is acknowledged by the receiver before the next batch is sent. A
+/// batch is sent when it holds [`FluentSink::batch_size`] records, when the
+/// sink is flushed and when it is dropped. Wrap it in a
+/// [`NonBlocking`](crate::NonBlocking) sink, which flushes it after every batch
+/// of lines and when idle, so the threads that log do not wait for
+/// acknowledgements.
+///
+/// Tags are derived from the module a line was logged from, with `::` replaced
+/// by `.` and an optional prefix: `billing::db` becomes `app.billing.db` with
+/// the prefix `app`. Records carry the level, the message, the key-value pairs
+/// and the source location as fields (`level`, `message`, `mod` and `src`).
+/// Keys named `level`, `mod` or `src` get a `_` in front. The
+/// [`Logger`](crate::Logger)'s format is not used.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{FluentSink, Logger, NonBlocking};
+///
+/// let (fluent, _guard) =
+///   NonBlocking::new(FluentSink::tcp("127.0.0.1:24224").tag_prefix("app"));
+/// Logger::default().sink(fluent).init();
+/// ```
+#[derive(Debug)]
+pub struct FluentSink {
+  transport: Transport,
+  tag_prefix: String,
+  batch_size: usize,
+  ack: bool,
+  timeout: Duration,
+  pending: Mutex<Vec<Record>>,
+  connection: Mutex<Option<Connection>>,
+  dropped: AtomicU64,
+}
+
+impl FluentSink {
+  /// Connect to `addr` (like `"127.0.0.1:24224"`) over TCP.
+  pub fn tcp(addr: &str) -> Self {
+    Self::new(Transport::Tcp(addr.to_owned()))
+  }
+
+  /// Connect to the Unix socket at `path`.
+  #[cfg(unix)]
+  pub fn unix<P: Into<PathBuf>>(path: P) -> Self {
+    Self::new(Transport::Unix(path.into()))
+  }
+
+  fn new(transport: Transport) -> Self {
+    Self {
+      transport,
+      tag_prefix: String::new(),
+      batch_size: 1024,
+      ack: true,
+      timeout: Duration::from_secs(5),
+      pending: Mutex::new(Vec::new()),
+      connection: Mutex::new(None),
+      dropped: AtomicU64::new(0),
+    }
+  }
+
+  /// Prefix tags with `prefix` and a `.`. Empty by default.
+  pub fn tag_prefix(mut self, prefix: &str) -> Self {
+    self.tag_prefix = prefix.to_owned();
+    self
+  }
+
+  /// Send a batch once it holds this many records. Defaults to 1024.
+  pub fn batch_size(mut self, batch_size: usize) -> Self {
+    self.batch_size = batch_size.max(1);
+    self
+  }
+
+  /// Ask the receiver to acknowledge every message. Enabled by default.
+  pub fn ack(mut self, ack: bool) -> Self {
+    self.ack = ack;
+    self
+  }
+
+  /// How long connecting, sending and waiting for acknowledgements may take.
+  /// Defaults to 5 seconds.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Number of records dropped because they could not be delivered.
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  fn tag(&self, module: &str) -> String {
+    let module = module.replace("::", ".");
+    if self.tag_prefix.is_empty() {
+      module
+    } else {
+      format!("{}.{module}", self.tag_prefix)
+    }
+  }
+
+  fn connect(&self) -> io::Result<Connection> {
+    let connection = match &self.transport {
+      Transport::Tcp(addr) => {
+        let stream = connect_any(addr, |addr| {
+          TcpStream::connect_timeout(&addr, self.timeout)
+        })?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        Connection::Tcp(stream)
+      }
+      #[cfg(unix)]
+      Transport::Unix(path) => {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        Connection::Unix(stream)
+      }
+    };
+
+    Ok(connection)
+  }
+
+  /// Encodes `batch` into Forward mode messages, one per run of records with
+  /// the same tag. Returns the chunk IDs to expect acknowledgements for.
+  fn encode(
+    &self,
+    batch: &[Record],
+    w: &mut Vec<u8>,
+  ) -> io::Result<Vec<String>> {
+    let mut chunks = vec![];
+    let mut rest = batch;
+
+    while let Some(first) = rest.first() {
+      let len = rest
+        .iter()
+        .position(|record| record.module != first.module)
+        .unwrap_or(rest.len());
+      let (entries, tail) = rest.split_at(len);
+      rest = tail;
+
+      write_array_len(w, 3)?;
+      write_str(w, &self.tag(&first.module))?;
+      write_array_len(w, entries.len())?;
+      for record in entries {
+        entry(w, record)?;
+      }
+
+      write_map_len(w, if self.ack { 2 } else { 1 })?;
+      write_str(w, "size")?;
+      write_uint(w, entries.len() as u64)?;
+      if self.ack {
+        let chunk = chunk_id();
+        write_str(w, "chunk")?;
+        write_str(w, &chunk)?;
+        chunks.push(chunk);
+      }
+    }
+
+    Ok(chunks)
+  }
+
+  fn send(&self, batch: &[Record]) -> io::Result<()> {
+    if batch.is_empty() {
+      return Ok(());
+    }
+
+    let mut messages = vec![];
+    let chunks = self.encode(batch, &mut messages)?;
+
+    let mut connection = self.connection.lock();
+    let mut result = Err(io::Error::new(io::ErrorKind::Other, "Not sent"));
+    // Reconnect and try again once if the connection broke
+    for _ in 0..2 {
+      let c = match connection.as_mut() {
+        Some(c) => c,
+        None => match self.connect() {
+          Ok(c) => connection.insert(c),
+          Err(e) => {
+            result = Err(e);
+            break;
+          }
+        },
+      };
+
+      result = c.send(&messages, &chunks);
+      if result.is_ok() {
+        break;
+      }
+      *connection = None;
+    }
+
+    if result.is_err() {
+      self
+        .dropped
+        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+    }
+    result
+  }
+}
+
+impl Connection {
+  fn send(&mut self, messages: &[u8], chunks: &[String]) -> io::Result<()> {
+    self.write_all(messages)?;
+    self.flush()?;
+
+    for chunk in chunks {
+      let response = read_str_map(self)?;
+      let acked = response
+        .iter()
+        .any(|(k, v)| k == "ack" && v.as_deref() == Some(chunk.as_str()));
+      if !acked {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          "Unexpected acknowledgement",
+        ));
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Read for Connection {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self {
+      Self::Tcp(stream) => stream.read(buf),
+      #[cfg(unix)]
+      Self::Unix(stream) => stream.read(buf),
+    }
+  }
+}
+
+impl Write for Connection {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match self {
+      Self::Tcp(stream) => stream.write(buf),
+      #[cfg(unix)]
+      Self::Unix(stream) => stream.write(buf),
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match self {
+      Self::Tcp(stream) => stream.flush(),
+      #[cfg(unix)]
+      Self::Unix(stream) => stream.flush(),
+    }
+  }
+}
+
+impl Sink for FluentSink {
+  fn log(&self, log: &Log, _: &[u8]) -> io::Result<()> {
+    let batch = {
+      let mut pending = self.pending.lock();
+      pending.push(log.to_owned());
+      if pending.len() < self.batch_size {
+        return Ok(());
+      }
+      std::mem::take(&mut *pending)
+    };
+
+    self.send(&batch)
+  }
+
+  fn flush(&self) -> io::Result<()> {
+    let batch = std::mem::take(&mut *self.pending.lock());
+    self.send(&batch)
+  }
+}
+
+impl Drop for FluentSink {
+  fn drop(&mut self) {
+    _ = self.flush();
+  }
+}
+
+/// Fields every record has. Key-value pairs with these keys get a `_` in
+/// front.
+const RESERVED: &[&str] = &["level", "mod", "src"];
+
+/// Writes `[time, record]`, with the time as an `EventTime`. `EventTime`s
+/// cannot be before the Unix epoch, so earlier times are written as the epoch.
+fn entry(w: &mut Vec<u8>, record: &Record) -> io::Result<()> {
+  write_array_len(w, 2)?;
+
+  let t = record.timestamp;
+  let (seconds, nanos) = match u32::try_from(t.unix_timestamp()) {
+    Ok(seconds) => (seconds, t.nanosecond()),
+    Err(_) if t.unix_timestamp() < 0 => (0, 0),
+    Err(_) => (u32::MAX, t.nanosecond()),
+  };
+  let mut time = [0; 8];
+  time[..4].copy_from_slice(&seconds.to_be_bytes());
+  time[4..].copy_from_slice(&nanos.to_be_bytes());
+  write_fixext8(w, 0, time)?;
+
+  write_map_len(w, record.kv.len() + 3)?;
+  write_str(w, "level")?;
+  write_str(w, &format!("{:?}", record.level))?;
+  for (k, v) in record.kv.iter() {
+    if RESERVED.contains(&k) {
+      write_str(w, &format!("_{k}"))?;
+    } else {
+      write_str(w, k)?;
+    }
+    write_str(w, v)?;
+  }
+  write_str(w, "mod")?;
+  write_str(w, &record.module)?;
+  write_str(w, "src")?;
+  write_str(w, &format!("{}:{}", record.file, record.line))
+}
+
+/// A unique ID for a chunk, base64-encoded.
+fn chunk_id() -> String {
+  static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+  let state = RandomState::new();
+  let mut id = [0; 16];
+  for (i, bytes) in id.chunks_mut(8).enumerate() {
+    let mut hasher = state.build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_usize(i);
+    bytes.copy_from_slice(&hasher.finish().to_le_bytes());
+  }
+
+  base64(&id)
+}
+
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+  let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{net::TcpListener, thread};
+
+  use super::*;
+
+  /// Decoded MessagePack, as much as the Forward protocol uses
+  #[derive(Debug, PartialEq)]
+  enum Value {
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Str(String),
+    Time(u32, u32),
+    Uint(u8),
+  }
+
+  fn decode<R: Read>(r: &mut R) -> Value {
+    let mut byte = [0; 1];
+    r.read_exact(&mut byte).unwrap();
+    let mut read = |len| {
+      let mut buf = vec![0; len];
+      r.read_exact(&mut buf).unwrap();
+      buf
+    };
+
+    match byte[0] {
+      b @ 0x00..=0x7f => Value::Uint(b),
+      b @ 0x90..=0x9f => {
+        Value::Array((0..b & 0x0f).map(|_| decode(r)).collect())
+      }
+      b @ 0x80..=0x8f => {
+        Value::Map((0..b & 0x0f).map(|_| (decode(r), decode(r))).collect())
+      }
+      b @ 0xa0..=0xbf => {
+        Value::Str(String::from_utf8(read((b & 0x1f) as usize)).unwrap())
+      }
+      0xd9 => {
+        let len = read(1)[0] as usize;
+        Value::Str(String::from_utf8(read(len)).unwrap())
+      }
+      0xd7 => {
+        let data = read(9);
+        assert_eq!(data[0], 0);
+        Value::Time(
+          u32::from_be_bytes(data[1..5].try_into().unwrap()),
+          u32::from_be_bytes(data[5..9].try_into().unwrap()),
+        )
+      }
+      b => panic!("Unexpected byte {b:x}"),
+    }
+  }
+
+  fn str(s: &str) -> Value {
+    Value::Str(s.to_owned())
+  }
+
+  #[test]
+  fn forward_mode() {
+    let fluentd = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = fluentd.local_addr().unwrap().to_string();
+
+    let receiver = thread::spawn(move || {
+      let (mut stream, _) = fluentd.accept().unwrap();
+      let mut messages = vec![];
+      for _ in 0..2 {
+        let message = decode(&mut stream);
+        let Value::Array(parts) = &message else {
+          panic!()
+        };
+        let Value::Map(options) = &parts[2] else {
+          panic!()
+        };
+        assert_eq!(options[0].0, str("size"));
+        let Value::Str(chunk) = &options[1].1 else {
+          panic!()
+        };
+
+        write_map_len(&mut stream, 1).unwrap();
+        write_str(&mut stream, "ack").unwrap();
+        write_str(&mut stream, chunk).unwrap();
+        messages.push(message);
+      }
+      messages
+    });
+
+    let sink = FluentSink::tcp(&addr).tag_prefix("app").batch_size(10);
+    let records = [
+      ("billing::db", "Connected"),
+      ("billing::db", "Queried"),
+      ("billing::http", "Listening"),
+    ];
+    for (module, message) in records {
+      Record {
+        timestamp: time::OffsetDateTime::UNIX_EPOCH,
+        level: crate::LogLevel::Info,
+        kv: vec![("count", "1".to_owned()), ("message", message.to_owned())]
+          .into(),
+        module: module.into(),
+        file: "fluent.rs".into(),
+        line: 10,
+      }
+      .with_log(|log| sink.log(log, b""))
+      .unwrap();
+    }
+    sink.flush().unwrap();
+
+    let messages = receiver.join().unwrap();
+    let Value::Array(db) = &messages[0] else {
+      panic!()
+    };
+    assert_eq!(db[0], str("app.billing.db"));
+    assert_eq!(
+      db[1],
+      Value::Array(
+        ["Connected", "Queried"]
+          .into_iter()
+          .map(|message| Value::Array(vec![
+            Value::Time(0, 0),
+            Value::Map(vec![
+              (str("level"), str("info")),
+              (str("count"), str("1")),
+              (str("message"), str(message)),
+              (str("mod"), str("billing::db")),
+              (str("src"), str("fluent.rs:10")),
+            ])
+          ]))
+          .collect()
+      )
+    );
+
+    let Value::Array(http) = &messages[1] else {
+      panic!()
+    };
+    assert_eq!(http[0], str("app.billing.http"));
+    assert_eq!(sink.dropped(), 0);
+  }
+
+  #[test]
+  fn entries() {
+    let record = Record {
+      timestamp: time::OffsetDateTime::UNIX_EPOCH
+        - time::Duration::milliseconds(1500),
+      level: crate::LogLevel::Warn,
+      kv: vec![
+        ("level", "custom".to_owned()),
+        ("src", "db".to_owned()),
+        ("message", "Early".to_owned()),
+      ]
+      .into(),
+      module: "billing".into(),
+      file: "fluent.rs".into(),
+      line: 10,
+    };
+
+    let mut w = vec![];
+    entry(&mut w, &record).unwrap();
+    assert_eq!(
+      decode(&mut w.as_slice()),
+      Value::Array(vec![
+        Value::Time(0, 0),
+        Value::Map(vec![
+          (str("level"), str("warn")),
+          (str("_level"), str("custom")),
+          (str("_src"), str("db")),
+          (str("message"), str("Early")),
+          (str("mod"), str("billing")),
+          (str("src"), str("fluent.rs:10")),
+        ])
+      ])
+    );
+  }
+
+  #[test]
+  fn unacknowledged() {
+    let fluentd = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = fluentd.local_addr().unwrap().to_string();
+    let receiver = thread::spawn(move || {
+      // Hang up without acknowledging, twice
+      for _ in 0..2 {
+        let (mut stream, _) = fluentd.accept().unwrap();
+        decode(&mut stream);
+      }
+    });
+
+    let sink = FluentSink::tcp(&addr);
+    let log = |message| {
+      sink.log(
+        &Log {
+          timestamp: time::OffsetDateTime::UNIX_EPOCH,
+          level: crate::LogLevel::Warn,
+          kv: &[("message", format_args!("{message}"))],
+          module: "tests",
+          file: "fluent.rs",
+          line: 10,
+        },
+        b"",
+      )
+    };
+
+    log("Lost").unwrap();
+    assert!(sink.flush().is_err());
+    receiver.join().unwrap();
+    assert_eq!(sink.dropped(), 1);
+
+    // Nothing listens anymore
+    log("Also lost").unwrap();
+    log("Lost too").unwrap();
+    assert!(sink.flush().is_err());
+    assert_eq!(sink.dropped(), 3);
+    assert!(sink.pending.lock().is_empty());
+  }
+
+  #[test]
+  fn encodes_base64() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    assert_eq!(chunk_id().len(), 24);
+    assert_ne!(chunk_id(), chunk_id());
+  }
+}
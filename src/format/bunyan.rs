@@ -0,0 +1,187 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{
+    escape::{json_key, Escape},
+    syslog::hostname,
+    Formatter,
+  },
+  log::{Log, LogLevel},
+  timestamp::{Precision, TimestampFormat},
+};
+
+/// Keys every line has. User fields with these names get a `_` in front.
+const RESERVED: &[&str] = &[
+  "level", "time", "name", "hostname", "pid", "msg", "src", "v",
+];
+
+/// JSON compatible with [bunyan](https://github.com/trentm/node-bunyan) and
+/// [pino](https://getpino.io), so their viewers (`bunyan`, `pino-pretty`) can
+/// read it.
+///
+/// Lines have a numeric `level` (`Trace` is 10, `Fatal` is 60), `time`, `msg`,
+/// `name`, `hostname`, `pid`, `v` and the key-value pairs, with the module,
+/// file and line in bunyan's `src` object. Fields named like one of these get
+/// a `_` in front, like `_name`. `time` is in milliseconds since the
+/// Unix epoch like pino writes it, the `bunyan` CLI wants RFC 3339 instead (see
+/// [`Bunyan::iso_time`]). Either way, the [`Logger`](crate::Logger)'s timestamp
+/// format does not apply.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{format::Bunyan, LogFormat, Logger};
+///
+/// Logger::default().format(LogFormat::Bunyan(Bunyan::new("billing")));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bunyan {
+  name: String,
+  hostname: String,
+  pid: u32,
+  iso_time: bool,
+}
+
+impl Bunyan {
+  /// Log as the application `name`.
+  pub fn new(name: &str) -> Self {
+    Self {
+      name: name.to_owned(),
+      hostname: hostname().unwrap_or_default(),
+      pid: std::process::id(),
+      iso_time: false,
+    }
+  }
+
+  pub fn hostname(mut self, hostname: &str) -> Self {
+    self.hostname = hostname.to_owned();
+    self
+  }
+
+  /// Write `time` as an RFC 3339 string, like bunyan does.
+  pub fn iso_time(mut self, iso_time: bool) -> Self {
+    self.iso_time = iso_time;
+    self
+  }
+}
+
+fn level(level: LogLevel) -> u8 {
+  match level {
+    LogLevel::Trace => 10,
+    LogLevel::Debug => 20,
+    LogLevel::Info => 30,
+    LogLevel::Warn => 40,
+    LogLevel::Error => 50,
+    LogLevel::Fatal => 60,
+  }
+}
+
+impl Formatter for Bunyan {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    write!(w, "{{\"level\":{},\"time\":", level(log.level))?;
+    if self.iso_time {
+      write!(w, "\"")?;
+      TimestampFormat::Rfc3339(Precision::Millis).write(
+        w,
+        &log.timestamp,
+        false,
+      )?;
+      write!(w, "\"")?;
+    } else {
+      TimestampFormat::Unix(Precision::Millis).write(
+        w,
+        &log.timestamp,
+        false,
+      )?;
+    }
+
+    write!(w, ",\"name\":\"")?;
+    write!(Escape::json(w), "{}", self.name)?;
+    write!(w, "\",\"hostname\":\"")?;
+    write!(Escape::json(w), "{}", self.hostname)?;
+    write!(w, "\",\"pid\":{},\"msg\":\"", self.pid)?;
+    write!(Escape::json(w), "{}", message.1)?;
+    write!(w, "\"")?;
+
+    for (k, v) in kv {
+      write!(w, ",\"")?;
+      json_key(w, k, RESERVED)?;
+      write!(w, "\":\"")?;
+      write!(Escape::json(w), "{v}")?;
+      write!(w, "\"")?;
+    }
+
+    write!(w, ",\"src\":{{\"file\":\"")?;
+    write!(Escape::json(w), "{}", log.file)?;
+    write!(w, "\",\"line\":{},\"func\":\"", log.line)?;
+    write!(Escape::json(w), "{}", log.module)?;
+    writeln!(w, "\"}},\"v\":0}}")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+
+  #[test]
+  fn bunyan() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH
+        + time::Duration::milliseconds(1500),
+      level: LogLevel::Fatal,
+      kv: &[
+        ("req_id", format_args!("42")),
+        ("message", format_args!("Out of \"memory\"")),
+      ],
+      module: "api",
+      file: "bunyan.rs",
+      line: 10,
+    };
+    let bunyan = Bunyan::new("billing").hostname("fra1");
+    let pid = std::process::id();
+
+    let mut w = vec![];
+    bunyan.format(&log, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      format!(
+        "{{\"level\":60,\"time\":1500,\"name\":\"billing\",\"hostname\":\"fra1\",\"pid\":{pid},\"msg\":\"Out of \\\"memory\\\"\",\"req_id\":\"42\",\"src\":{{\"file\":\"bunyan.rs\",\"line\":10,\"func\":\"api\"}},\"v\":0}}\n"
+      )
+    );
+
+    let mut w = vec![];
+    bunyan.iso_time(true).format(&log, &mut w).unwrap();
+    assert!(String::from_utf8(w)
+      .unwrap()
+      .starts_with("{\"level\":60,\"time\":\"1970-01-01T00:00:01.500Z\","));
+  }
+
+  #[test]
+  fn collisions() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("name", format_args!("worker")),
+        ("v", format_args!("{}", 2)),
+        ("message", format_args!("Collided")),
+      ],
+      module: "api",
+      file: "bunyan.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Bunyan::new("billing").format(&log, &mut w).unwrap();
+    let line = String::from_utf8(w).unwrap();
+    assert!(
+      line.contains(r#""msg":"Collided","_name":"worker","_v":"2","src":"#)
+    );
+    assert_eq!(line.matches("\"name\"").count(), 1);
+  }
+}
@@ -0,0 +1,47 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{escape::Escape, Formatter},
+  log::Log,
+  timestamp::TimestampFormat,
+};
+
+/// Plain `key=value` lines, without colors.
+///
+/// Values are written as they are, except for control characters, which are
+/// escaped. Use [`Logfmt`](crate::format::Logfmt) if lines need to be parsed
+/// by logfmt parsers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Compact {
+  pub timestamp: TimestampFormat,
+}
+
+impl Formatter for Compact {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    if self.timestamp != TimestampFormat::None {
+      write!(w, "ts=")?;
+      self.timestamp.write(w, &log.timestamp, false)?;
+      write!(w, " ")?;
+    }
+
+    write!(w, "level={:?} ", log.level)?;
+    write!(w, "message=\"")?;
+    write!(Escape::new(w), "{}", message.1)?;
+    write!(w, "\" ")?;
+    kv.iter().try_for_each(|(k, v)| {
+      write!(w, "{k}=")?;
+      write!(Escape::new(w), "{v}")?;
+      write!(w, " ")
+    })?;
+    write!(w, "mod={} ", log.module)?;
+    write!(w, "src={}:{}", log.file, log.line)?;
+
+    writeln!(w)?;
+
+    Ok(())
+  }
+}
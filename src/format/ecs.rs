@@ -0,0 +1,245 @@
+use std::{
+  fmt::Display,
+  io::{self, Write},
+};
+
+use crate::{
+  format::{escape::Escape, Formatter},
+  log::Log,
+  timestamp::TimestampFormat,
+};
+
+/// The version of ECS these documents follow
+const ECS_VERSION: &str = "8.11.0";
+
+/// JSON that follows the [Elastic Common Schema](https://www.elastic.co/guide/en/ecs/current/index.html),
+/// as written by Elastic's [ECS loggers](https://www.elastic.co/guide/en/ecs-logging/overview/current/intro.html).
+///
+/// `@timestamp`, `log.level` and `message` come first, then `ecs.version`, the
+/// module as `log.logger` and the source location as `log.origin.file.*`.
+/// Key-value pairs are nested according to their dotted names, so
+/// `http.request.method` ends up in `{"http":{"request":{"method":...}}}`.
+/// Keys that are already taken, like a second `url` or `log.level`, are moved
+/// under `labels` instead, where later values replace earlier ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ecs {
+  pub timestamp: TimestampFormat,
+}
+
+#[derive(Clone, Copy)]
+enum Value<'a> {
+  String(&'a dyn Display),
+  Number(u32),
+}
+
+enum Node<'a> {
+  Leaf(Value<'a>),
+  Object(Vec<(&'a str, Node<'a>)>),
+}
+
+/// Inserts `value` at the dotted path `key`. If part of the path is already
+/// taken by a value, the rest of the key is kept dotted instead. Returns
+/// `false` if the key itself is taken.
+fn insert<'a>(
+  nodes: &mut Vec<(&'a str, Node<'a>)>,
+  key: &'a str,
+  value: Value<'a>,
+) -> bool {
+  if nodes.iter().any(|(k, _)| *k == key) {
+    return false;
+  }
+
+  let Some((head, rest)) = key.split_once('.') else {
+    nodes.push((key, Node::Leaf(value)));
+    return true;
+  };
+
+  match nodes.iter_mut().find(|(k, _)| *k == head) {
+    Some((_, Node::Object(children))) => insert(children, rest, value),
+    Some((_, Node::Leaf(_))) => {
+      nodes.push((key, Node::Leaf(value)));
+      true
+    }
+    None => {
+      let mut children = vec![];
+      insert(&mut children, rest, value);
+      nodes.push((head, Node::Object(children)));
+      true
+    }
+  }
+}
+
+/// Inserts a key-value pair, or puts it under `labels` if its key is taken.
+/// It is dropped if `labels` itself is not an object.
+fn insert_field<'a>(
+  nodes: &mut Vec<(&'a str, Node<'a>)>,
+  key: &'a str,
+  value: Value<'a>,
+) {
+  // These are written ahead of the other fields
+  let reserved = matches!(key, "@timestamp" | "log.level" | "message");
+  if !reserved && insert(nodes, key, value) {
+    return;
+  }
+
+  let labels = match nodes.iter().position(|(k, _)| *k == "labels") {
+    Some(i) => &mut nodes[i].1,
+    None => {
+      nodes.push(("labels", Node::Object(vec![])));
+      &mut nodes.last_mut().unwrap().1
+    }
+  };
+  if let Node::Object(labels) = labels {
+    match labels.iter_mut().find(|(k, _)| *k == key) {
+      Some((_, node)) => *node = Node::Leaf(value),
+      None => labels.push((key, Node::Leaf(value))),
+    }
+  }
+}
+
+fn write_nodes(
+  w: &mut dyn io::Write,
+  nodes: &[(&str, Node)],
+) -> io::Result<()> {
+  for (i, (key, node)) in nodes.iter().enumerate() {
+    if i > 0 {
+      write!(w, ",")?;
+    }
+
+    write!(w, "\"")?;
+    write!(Escape::json(w), "{key}")?;
+    write!(w, "\":")?;
+    match node {
+      Node::Leaf(Value::String(s)) => {
+        write!(w, "\"")?;
+        write!(Escape::json(w), "{s}")?;
+        write!(w, "\"")?;
+      }
+      Node::Leaf(Value::Number(n)) => write!(w, "{n}")?,
+      Node::Object(children) => {
+        write!(w, "{{")?;
+        write_nodes(w, children)?;
+        write!(w, "}}")?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+impl Formatter for Ecs {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    write!(w, "{{")?;
+
+    if self.timestamp.is_numeric() {
+      write!(w, "\"@timestamp\":")?;
+      self.timestamp.write(w, &log.timestamp, false)?;
+      write!(w, ",")?;
+    } else if self.timestamp != TimestampFormat::None {
+      write!(w, "\"@timestamp\":\"")?;
+      // Custom formats can contain anything, including quotes
+      self
+        .timestamp
+        .write(&mut Escape::json(w), &log.timestamp, false)?;
+      write!(w, "\",")?;
+    }
+
+    write!(w, "\"log.level\":\"{:?}\",\"message\":\"", log.level)?;
+    write!(Escape::json(w), "{}", message.1)?;
+    write!(w, "\",")?;
+
+    let mut nodes = vec![];
+    insert(&mut nodes, "ecs.version", Value::String(&ECS_VERSION));
+    insert(&mut nodes, "log.logger", Value::String(&log.module));
+    insert(&mut nodes, "log.origin.file.name", Value::String(&log.file));
+    insert(&mut nodes, "log.origin.file.line", Value::Number(log.line));
+    for (k, v) in kv {
+      insert_field(&mut nodes, k, Value::String(v));
+    }
+    write_nodes(w, &nodes)?;
+
+    writeln!(w, "}}")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+  use crate::LogLevel;
+
+  #[test]
+  fn nested() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Warn,
+      kv: &[
+        ("http.request.method", format_args!("GET")),
+        ("http.response.status_code", format_args!("{}", 503)),
+        ("log.origin.function", format_args!("handle")),
+        ("url", format_args!("/")),
+        ("url.path", format_args!("/\"quoted\"")),
+        ("message", format_args!("Upstream unavailable")),
+      ],
+      module: "api::proxy",
+      file: "ecs.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Ecs::default().format(&log, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [
+        r#"{"@timestamp":"1970-01-01T00:00:00.000Z","log.level":"warn","message":"Upstream unavailable","#,
+        r#""ecs":{"version":"8.11.0"},"#,
+        r#""log":{"logger":"api::proxy","origin":{"file":{"name":"ecs.rs","line":10},"function":"handle"}},"#,
+        r#""http":{"request":{"method":"GET"},"response":{"status_code":"503"}},"#,
+        r#""url":"/","url.path":"/\"quoted\""}"#,
+        "\n"
+      ]
+      .concat()
+    );
+  }
+
+  #[test]
+  fn collisions() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("url", format_args!("/")),
+        ("url", format_args!("/again")),
+        ("url.path", format_args!("/path")),
+        ("url.path", format_args!("/path/again")),
+        ("log.level", format_args!("custom")),
+        ("ecs.version", format_args!("1.0")),
+        ("url", format_args!("/last")),
+        ("message", format_args!("Collided")),
+      ],
+      module: "api",
+      file: "ecs.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Ecs::default().format(&log, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [
+        r#"{"@timestamp":"1970-01-01T00:00:00.000Z","log.level":"info","message":"Collided","#,
+        r#""ecs":{"version":"8.11.0"},"#,
+        r#""log":{"logger":"api","origin":{"file":{"name":"ecs.rs","line":10}}},"#,
+        r#""url":"/","labels":{"url":"/last","url.path":"/path/again","log.level":"custom","ecs.version":"1.0"},"#,
+        r#""url.path":"/path"}"#,
+        "\n"
+      ]
+      .concat()
+    );
+  }
+}
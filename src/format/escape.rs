@@ -0,0 +1,185 @@
+use std::{cell::Cell, io};
+
+thread_local! {
+  static SCRATCH: Cell<Vec<u8>> = const { Cell::new(Vec::new()) };
+}
+
+/// Calls `f` with an empty buffer, for values that need to be rendered before
+/// they can be written (to pad or quote them, for example). The buffer is
+/// reused across calls on the same thread.
+pub(crate) fn with_scratch<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+  let mut scratch = SCRATCH.try_with(Cell::take).unwrap_or_default();
+  scratch.clear();
+  let result = f(&mut scratch);
+  _ = SCRATCH.try_with(|s| s.set(scratch));
+  result
+}
+
+/// A writer that escapes control characters on their way to `inner`, so
+/// values cannot start new log lines or smuggle terminal escape sequences.
+///
+/// Newlines, carriage returns and tabs become `\n`, `\r` and `\t`, other
+/// control characters become `\x1b` or `\u{9b}`. Quoted escapes also put a
+/// backslash in front of `"` and `\`. JSON escapes produce valid JSON string
+/// contents instead, with other control characters as `\u001b`.
+pub(crate) struct Escape<'a> {
+  inner: &'a mut dyn io::Write,
+  /// ASCII characters to put a backslash in front of
+  backslashed: &'static [u8],
+  json: bool,
+}
+
+impl<'a> Escape<'a> {
+  pub(crate) fn new(inner: &'a mut dyn io::Write) -> Self {
+    Self::backslashing(inner, b"")
+  }
+
+  pub(crate) fn quoted(inner: &'a mut dyn io::Write) -> Self {
+    Self::backslashing(inner, b"\"\\")
+  }
+
+  pub(crate) fn backslashing(
+    inner: &'a mut dyn io::Write,
+    backslashed: &'static [u8],
+  ) -> Self {
+    Self {
+      inner,
+      backslashed,
+      json: false,
+    }
+  }
+
+  pub(crate) fn json(inner: &'a mut dyn io::Write) -> Self {
+    Self {
+      json: true,
+      ..Self::quoted(inner)
+    }
+  }
+}
+
+/// Whether `bytes[i]` starts a C1 control character (U+0080 to U+009F), which
+/// some terminals interpret like escape sequences.
+fn is_c1(bytes: &[u8], i: usize) -> bool {
+  bytes[i] == 0xc2 && matches!(bytes.get(i + 1), Some(0x80..=0x9f))
+}
+
+fn is_control(b: u8) -> bool {
+  b < 0x20 || b == 0x7f
+}
+
+impl io::Write for Escape<'_> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.write_all(buf)?;
+    Ok(buf.len())
+  }
+
+  fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < buf.len() {
+      let b = buf[i];
+      let backslashed = self.backslashed.contains(&b);
+      let c1 = !self.json && is_c1(buf, i);
+      if !is_control(b) && !backslashed && !c1 {
+        i += 1;
+        continue;
+      }
+
+      self.inner.write_all(&buf[start..i])?;
+      match b {
+        b'\n' => self.inner.write_all(b"\\n")?,
+        b'\r' => self.inner.write_all(b"\\r")?,
+        b'\t' => self.inner.write_all(b"\\t")?,
+        _ if backslashed => self.inner.write_all(&[b'\\', b])?,
+        _ if self.json => write!(self.inner, "\\u{b:04x}")?,
+        0xc2 => {
+          // Safe to index, `is_c1` checked that the byte exists
+          write!(self.inner, "\\u{{{:x}}}", buf[i + 1])?;
+          i += 1;
+        }
+        _ => write!(self.inner, "\\x{b:02x}")?,
+      }
+
+      i += 1;
+      start = i;
+    }
+
+    self.inner.write_all(&buf[start..])
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+/// Whether a logfmt value has to be quoted.
+pub(crate) fn needs_quotes(value: &[u8]) -> bool {
+  value.is_empty()
+    || value.iter().enumerate().any(|(i, b)| {
+      *b <= b' ' || *b == b'=' || *b == b'"' || *b == 0x7f || is_c1(value, i)
+    })
+}
+
+/// Writes `key` as JSON string contents, with a `_` in front if it is one of
+/// the `reserved` keys a format writes itself, so user fields never produce
+/// duplicate keys.
+pub(crate) fn json_key(
+  w: &mut dyn io::Write,
+  key: &str,
+  reserved: &[&str],
+) -> io::Result<()> {
+  if reserved.contains(&key) {
+    w.write_all(b"_")?;
+  }
+  io::Write::write_all(&mut Escape::json(w), key.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use super::*;
+
+  fn escape(value: &str, quoted: bool) -> String {
+    let mut w = vec![];
+    if quoted {
+      write!(Escape::quoted(&mut w), "{value}").unwrap();
+    } else {
+      write!(Escape::new(&mut w), "{value}").unwrap();
+    }
+    String::from_utf8(w).unwrap()
+  }
+
+  #[test]
+  fn json() {
+    let mut w = vec![];
+    write!(Escape::json(&mut w), "say \"hi\\\"\n\x1b[31m\u{9b}").unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      "say \\\"hi\\\\\\\"\\n\\u001b[31m\u{9b}"
+    );
+  }
+
+  #[test]
+  fn control_characters() {
+    assert_eq!(
+      escape("a\nlevel=fatal\r\t\x1b[31m\u{9b}é\u{a0}", false),
+      "a\\nlevel=fatal\\r\\t\\x1b[31m\\u{9b}é\u{a0}"
+    );
+    assert_eq!(escape(r#"say "hi\" "#, false), r#"say "hi\" "#);
+    assert_eq!(escape(r#"say "hi\" "#, true), r#"say \"hi\\\" "#);
+  }
+
+  #[test]
+  fn quotes() {
+    assert!(!needs_quotes(b"value"));
+    assert!(!needs_quotes("é\\".as_bytes()));
+    assert!(needs_quotes(b""));
+    assert!(needs_quotes(b"two words"));
+    assert!(needs_quotes(b"a=b"));
+    assert!(needs_quotes(b"\"a\""));
+    assert!(needs_quotes(b"a\nb"));
+    assert!(needs_quotes("\u{85}".as_bytes()));
+  }
+}
@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{
+    escape::Escape,
+    syslog::{hostname, severity},
+    Formatter,
+  },
+  log::Log,
+};
+
+/// [GELF](https://go2docs.graylog.org/current/getting_in_log_data/gelf.html),
+/// the Graylog Extended Log Format.
+///
+/// The message becomes `short_message`, the level a syslog severity in
+/// `level`, and the timestamp is written in seconds with millisecond
+/// precision (the [`Logger`](crate::Logger)'s timestamp format does not
+/// apply). Key-value pairs become additional fields, prefixed with `_`, along
+/// with `_module`, `_file` and `_line`. Characters GELF does not allow in
+/// field names become `_`. `id` (which Graylog reserves), `module`, `file`
+/// and `line` get another `_` in front, like `__id`.
+///
+/// Lines end in a newline like other formats. Send them to Graylog with a
+/// [`GelfSink`](crate::GelfSink).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gelf {
+  host: String,
+}
+
+impl Gelf {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Defaults to the hostname of this machine.
+  pub fn host(mut self, host: &str) -> Self {
+    self.host = host.to_owned();
+    self
+  }
+}
+
+impl Default for Gelf {
+  fn default() -> Self {
+    Self {
+      host: hostname().unwrap_or_else(|| String::from("localhost")),
+    }
+  }
+}
+
+/// Additional fields Graylog or this format claim, which user fields with the
+/// same name would collide with. Like other JSON formats, those get another
+/// `_` in front.
+const RESERVED: &[&str] = &["id", "module", "file", "line"];
+
+fn write_field(w: &mut dyn io::Write, key: &str) -> io::Result<()> {
+  write!(w, ",\"_")?;
+  if RESERVED.contains(&key) {
+    write!(w, "_")?;
+  }
+  for c in key.chars() {
+    let valid = c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-');
+    write!(w, "{}", if valid { c } else { '_' })?;
+  }
+  write!(w, "\":")
+}
+
+impl Formatter for Gelf {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    write!(w, "{{\"version\":\"1.1\",\"host\":\"")?;
+    write!(Escape::json(w), "{}", self.host)?;
+    write!(w, "\",\"short_message\":\"")?;
+    write!(Escape::json(w), "{}", message.1)?;
+    // Seconds as a decimal, which is negative before 1970
+    let millis = log.timestamp.unix_timestamp_nanos() / 1_000_000;
+    write!(
+      w,
+      "\",\"timestamp\":{}{}.{:03},\"level\":{}",
+      if millis < 0 { "-" } else { "" },
+      millis.abs() / 1000,
+      millis.abs() % 1000,
+      severity(log.level)
+    )?;
+
+    for (k, v) in kv {
+      write_field(w, k)?;
+      write!(w, "\"")?;
+      write!(Escape::json(w), "{v}")?;
+      write!(w, "\"")?;
+    }
+
+    write!(w, ",\"_module\":\"")?;
+    write!(Escape::json(w), "{}", log.module)?;
+    write!(w, "\",\"_file\":\"")?;
+    write!(Escape::json(w), "{}", log.file)?;
+    writeln!(w, "\",\"_line\":{}}}", log.line)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+  use crate::LogLevel;
+
+  #[test]
+  fn gelf() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH
+        + time::Duration::milliseconds(1500),
+      level: LogLevel::Warn,
+      kv: &[
+        ("id", format_args!("7")),
+        ("line", format_args!("{}", 3)),
+        ("http.status code", format_args!("{}", 503)),
+        ("message", format_args!("Upstream \"unavailable\"")),
+      ],
+      module: "api",
+      file: "gelf.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Gelf::new().host("fra1").format(&log, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [
+        r#"{"version":"1.1","host":"fra1","short_message":"Upstream \"unavailable\"","#,
+        r#""timestamp":1.500,"level":4,"__id":"7","__line":"3","_http.status_code":"503","#,
+        r#""_module":"api","_file":"gelf.rs","_line":10}"#,
+        "\n"
+      ]
+      .concat()
+    );
+
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH
+        - time::Duration::milliseconds(1500),
+      ..log
+    };
+    let mut w = vec![];
+    Gelf::new().format(&log, &mut w).unwrap();
+    assert!(String::from_utf8(w)
+      .unwrap()
+      .contains(r#""timestamp":-1.500,"#));
+  }
+}
@@ -0,0 +1,63 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{
+    escape::{json_key, Escape},
+    Formatter,
+  },
+  log::Log,
+  timestamp::TimestampFormat,
+};
+
+/// Keys every line has. User fields with these names get a `_` in front.
+const RESERVED: &[&str] = &["ts", "level", "message", "mod", "src"];
+
+/// One JSON object per line. Keys and values are escaped as JSON strings, and
+/// fields named like a built-in key get a `_` in front, like `_level`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json {
+  pub timestamp: TimestampFormat,
+}
+
+impl Formatter for Json {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    write!(w, "{{",)?;
+
+    if self.timestamp.is_numeric() {
+      write!(w, "\"ts\":")?;
+      self.timestamp.write(w, &log.timestamp, false)?;
+      write!(w, ",")?;
+    } else if self.timestamp != TimestampFormat::None {
+      write!(w, "\"ts\":\"")?;
+      // Custom formats can contain anything, including quotes
+      self
+        .timestamp
+        .write(&mut Escape::json(w), &log.timestamp, false)?;
+      write!(w, "\",")?;
+    }
+
+    write!(w, "\"level\":\"{:?}\",", log.level)?;
+    write!(w, "\"message\":\"")?;
+    write!(Escape::json(w), "{}", message.1)?;
+    for (k, v) in kv {
+      write!(w, "\",\"")?;
+      json_key(w, k, RESERVED)?;
+      write!(w, "\":\"")?;
+      write!(Escape::json(w), "{v}")?;
+    }
+    write!(w, "\",\"mod\":\"")?;
+    write!(Escape::json(w), "{}", log.module)?;
+    write!(w, "\",\"src\":\"")?;
+    write!(Escape::json(w), "{}:{}", log.file, log.line)?;
+    write!(w, "\"")?;
+
+    write!(w, "}}")?;
+    writeln!(w)?;
+
+    Ok(())
+  }
+}
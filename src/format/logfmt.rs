@@ -0,0 +1,122 @@
+use std::{
+  fmt::Display,
+  io::{self, Write},
+};
+
+use crate::{
+  format::{
+    escape::{needs_quotes, with_scratch, Escape},
+    Formatter,
+  },
+  log::Log,
+  timestamp::TimestampFormat,
+};
+
+/// Spec-compliant [logfmt](https://brandur.org/logfmt): `key=value` pairs,
+/// with values quoted and escaped where they need to be.
+///
+/// Unlike [`Compact`](crate::format::Compact), values containing spaces, `=`,
+/// quotes or control characters are quoted, so every line can be parsed back
+/// into the same pairs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logfmt {
+  pub timestamp: TimestampFormat,
+}
+
+impl Formatter for Logfmt {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    if self.timestamp != TimestampFormat::None {
+      w.write_all(b"ts=")?;
+      with_scratch(|scratch| {
+        self.timestamp.write(scratch, &log.timestamp, false)?;
+        write_value(w, scratch)
+      })?;
+      w.write_all(b" ")?;
+    }
+
+    write!(w, "level={:?} ", log.level)?;
+    pair(w, "message", message.1)?;
+    for (k, v) in kv {
+      w.write_all(b" ")?;
+      pair(w, k, v)?;
+    }
+    w.write_all(b" ")?;
+    pair(w, "mod", log.module)?;
+    w.write_all(b" ")?;
+    pair(w, "src", format_args!("{}:{}", log.file, log.line))?;
+
+    writeln!(w)
+  }
+}
+
+fn pair(
+  w: &mut dyn io::Write,
+  key: &str,
+  value: impl Display,
+) -> io::Result<()> {
+  // Keys cannot be quoted, so characters that are not allowed in them are
+  // replaced instead
+  for c in key.chars() {
+    if c <= ' ' || c == '=' || c == '"' || c.is_control() {
+      w.write_all(b"_")?;
+    } else {
+      let mut buf = [0; 4];
+      w.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+    }
+  }
+  w.write_all(b"=")?;
+
+  with_scratch(|scratch| {
+    write!(scratch, "{value}")?;
+    write_value(w, scratch)
+  })
+}
+
+fn write_value(w: &mut dyn io::Write, value: &[u8]) -> io::Result<()> {
+  if needs_quotes(value) {
+    w.write_all(b"\"")?;
+    Escape::quoted(w).write_all(value)?;
+    w.write_all(b"\"")
+  } else {
+    w.write_all(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+  use crate::LogLevel;
+
+  #[test]
+  fn quotes_and_escapes() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("plain", format_args!("value")),
+        ("empty", format_args!("")),
+        ("spaces", format_args!("two words")),
+        ("debug", format_args!("{:?}", "quoted")),
+        ("forged", format_args!("x\nts=0 level=fatal")),
+        ("bad key", format_args!("a=b")),
+        ("message", format_args!("Hello, world")),
+      ],
+      module: "tests",
+      file: "logfmt.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Logfmt::default().format(&log, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      "ts=1970-01-01T00:00:00.000Z level=info message=\"Hello, world\" plain=value empty=\"\" spaces=\"two words\" debug=\"\\\"quoted\\\"\" forged=\"x\\nts=0 level=fatal\" bad_key=\"a=b\" mod=tests src=logfmt.rs:10\n"
+    );
+  }
+}
@@ -0,0 +1,93 @@
+//! Built-in log formats, and the [`Formatter`] trait to write your own.
+
+use std::{fmt, io};
+
+use crate::log::Log;
+
+mod bunyan;
+mod compact;
+mod ecs;
+pub(crate) mod escape;
+mod gelf;
+mod json;
+mod logfmt;
+mod pretty;
+mod stackdriver;
+pub(crate) mod syslog;
+mod template;
+
+pub use bunyan::Bunyan;
+pub use compact::Compact;
+pub use ecs::Ecs;
+pub use gelf::Gelf;
+pub use json::Json;
+pub use logfmt::Logfmt;
+pub use pretty::{Pretty, PrettyStructured};
+pub use stackdriver::Stackdriver;
+pub use syslog::{Facility, Rfc3164, Rfc5424, Syslog};
+pub use template::{Template, TemplateError};
+
+/// Turns a [`Log`] into bytes.
+///
+/// All of the built-in formats implement this trait. Implement it to use a
+/// format of your own with [`Logger::formatter`](crate::Logger::formatter).
+///
+/// Implementations write a complete line, including the trailing newline.
+/// Because the message is always the last key-value pair, it can be split off
+/// with `log.kv.split_last()`.
+///
+/// # Examples
+/// ```
+/// use std::io::{self, Write};
+///
+/// use lumbermill::{format::Formatter, Log, Logger};
+///
+/// struct Levels;
+///
+/// impl Formatter for Levels {
+///   fn format(&self, log: &Log, w: &mut dyn Write) -> io::Result<()> {
+///     let (message, _) = log.kv.split_last().expect("A log message is required");
+///     writeln!(w, "{}: {}", log.level, message.1)
+///   }
+/// }
+///
+/// Logger::default().formatter(Levels);
+/// ```
+pub trait Formatter: Send + Sync {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()>;
+}
+
+impl fmt::Debug for dyn Formatter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Formatter")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use super::*;
+  use crate::{info, testing::CaptureSink, Logger};
+
+  struct Upper;
+
+  impl Formatter for Upper {
+    fn format(&self, log: &Log, w: &mut dyn Write) -> io::Result<()> {
+      let (message, _) = log.kv.split_last().unwrap();
+      writeln!(w, "{}", message.1.to_string().to_uppercase())
+    }
+  }
+
+  #[test]
+  fn custom() {
+    let capture = CaptureSink::new();
+    let logger = Logger::new()
+      .stdout(false)
+      .formatter(Upper)
+      .sink(capture.clone());
+
+    info!(logger: &logger, key = "value", "Custom formats");
+    assert_eq!(capture.output(), "CUSTOM FORMATS\n");
+  }
+}
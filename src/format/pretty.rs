@@ -0,0 +1,111 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{escape::Escape, Formatter},
+  log::{Log, LogLevel},
+  timestamp::TimestampFormat,
+};
+
+// ANSI escape sequences used by the pretty formats. These are written directly
+// instead of through `owo_colors`' wrappers so formatting a line never needs to
+// allocate.
+pub(crate) const DIM: &str = "\x1b[2m";
+pub(crate) const RESET: &str = "\x1b[0m";
+pub(crate) const RED: &str = "\x1b[31m";
+pub(crate) const CYAN: &str = "\x1b[36m";
+pub(crate) const FG_RESET: &str = "\x1b[39m";
+
+/// Colorful, human-readable lines for terminals. Only the time of day is
+/// printed with RFC 3339 timestamps.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pretty {
+  pub timestamp: TimestampFormat,
+}
+
+impl Formatter for Pretty {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    if self.timestamp != TimestampFormat::None {
+      write!(w, "{DIM}")?;
+      self.timestamp.write(w, &log.timestamp, true)?;
+      write!(w, "{RESET} ")?;
+    }
+
+    write!(w, "{}{}{FG_RESET} ", log.level.ansi_fg(), log.level)?;
+
+    if log.level == LogLevel::Error || log.level == LogLevel::Fatal {
+      write!(w, "{RED}")?;
+      write!(Escape::new(w), "{}", message.1)?;
+      write!(w, "{FG_RESET} ")?;
+    } else {
+      write!(Escape::new(w), "{}", message.1)?;
+      write!(w, " ")?;
+    }
+
+    kv.iter().try_for_each(|(k, v)| {
+      write!(w, "{DIM}{k}{RESET}{DIM}={RESET}")?;
+      write!(Escape::new(w), "{v}")?;
+      write!(w, " ")
+    })?;
+
+    write!(w, "{DIM}mod={RESET}{} ", log.module)?;
+    write!(w, "{DIM}src={RESET}{}:{} ", log.file, log.line)?;
+
+    writeln!(w)?;
+
+    Ok(())
+  }
+}
+
+/// Like [`Pretty`], but with every field labelled, logfmt-style.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrettyStructured {
+  pub timestamp: TimestampFormat,
+}
+
+impl Formatter for PrettyStructured {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    if self.timestamp != TimestampFormat::None {
+      write!(w, "{DIM}ts={RESET}{DIM}")?;
+      self.timestamp.write(w, &log.timestamp, false)?;
+      write!(w, "{RESET} ")?;
+    }
+
+    write!(
+      w,
+      "{DIM}level={RESET}{}{:?}{FG_RESET} ",
+      log.level.ansi_fg(),
+      log.level
+    )?;
+
+    if log.level == LogLevel::Error || log.level == LogLevel::Fatal {
+      write!(w, "{DIM}message={RESET}\"{RED}")?;
+      write!(Escape::new(w), "{}", message.1)?;
+      write!(w, "{FG_RESET}\" ")?;
+    } else {
+      write!(w, "{DIM}message={RESET}\"")?;
+      write!(Escape::new(w), "{}", message.1)?;
+      write!(w, "\" ")?;
+    }
+
+    kv.iter().try_for_each(|(k, v)| {
+      write!(w, "{DIM}{k}{RESET}{DIM}={RESET}{CYAN}")?;
+      write!(Escape::new(w), "{v}")?;
+      write!(w, "{FG_RESET} ")
+    })?;
+
+    write!(w, "{DIM}mod={}{RESET} ", log.module)?;
+    write!(w, "{DIM}src={}:{}{RESET} ", log.file, log.line)?;
+
+    writeln!(w)?;
+
+    Ok(())
+  }
+}
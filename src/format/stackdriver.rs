@@ -0,0 +1,249 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{
+    escape::{json_key, Escape},
+    Formatter,
+  },
+  log::{Log, LogLevel},
+  timestamp::{Precision, TimestampFormat},
+};
+
+/// Keys every entry can have. User fields with these names get a `_` in front.
+const RESERVED: &[&str] = &[
+  "severity",
+  "message",
+  "time",
+  "timestampSeconds",
+  "timestampNanos",
+  "logging.googleapis.com/labels",
+  "logging.googleapis.com/sourceLocation",
+];
+
+/// JSON that Google Cloud's logging agents turn into
+/// [structured log entries](https://cloud.google.com/logging/docs/structured-logging),
+/// like on Cloud Run, GKE or App Engine.
+///
+/// Levels become `severity` (`Trace` and `Debug` both become `DEBUG`, `Fatal`
+/// becomes `CRITICAL`), the module, file and line become
+/// `logging.googleapis.com/sourceLocation`. Key-value pairs whose keys are in
+/// `labels` become `logging.googleapis.com/labels`, all others end up in the
+/// entry's `jsonPayload`. Fields named like a key the format writes itself get
+/// a `_` in front, like `_severity`.
+///
+/// Cloud Logging only understands RFC 3339 timestamps, written as `time`, and
+/// seconds and nanoseconds since the epoch, written as `timestampSeconds` and
+/// `timestampNanos`. Unix timestamp formats use the latter, custom formats fall
+/// back to RFC 3339 with millisecond precision.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{format::Stackdriver, LogFormat, Logger};
+///
+/// Logger::default().format(LogFormat::Stackdriver(Stackdriver {
+///   labels: &["tenant"],
+///   ..Default::default()
+/// }));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stackdriver {
+  /// Overridden by the [`Logger`](crate::Logger)'s timestamp format when used
+  /// as a [`LogFormat`](crate::LogFormat).
+  pub timestamp: TimestampFormat,
+  pub labels: &'static [&'static str],
+}
+
+fn severity(level: LogLevel) -> &'static str {
+  match level {
+    LogLevel::Trace | LogLevel::Debug => "DEBUG",
+    LogLevel::Info => "INFO",
+    LogLevel::Warn => "WARNING",
+    LogLevel::Error => "ERROR",
+    LogLevel::Fatal => "CRITICAL",
+  }
+}
+
+impl Formatter for Stackdriver {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    write!(
+      w,
+      "{{\"severity\":\"{}\",\"message\":\"",
+      severity(log.level)
+    )?;
+    write!(Escape::json(w), "{}", message.1)?;
+    write!(w, "\",")?;
+
+    match self.timestamp {
+      TimestampFormat::None => {}
+      TimestampFormat::Unix(precision) => {
+        let unit = match precision {
+          Precision::Seconds => 1_000_000_000,
+          Precision::Millis => 1_000_000,
+          Precision::Micros => 1_000,
+          Precision::Nanos => 1,
+        };
+        write!(
+          w,
+          "\"timestampSeconds\":{},\"timestampNanos\":{},",
+          log.timestamp.unix_timestamp(),
+          log.timestamp.nanosecond() / unit * unit
+        )?;
+      }
+      TimestampFormat::Rfc3339(_) | TimestampFormat::Custom(_) => {
+        let rfc3339 = match self.timestamp {
+          TimestampFormat::Custom(_) => {
+            TimestampFormat::Rfc3339(Precision::Millis)
+          }
+          timestamp => timestamp,
+        };
+        write!(w, "\"time\":\"")?;
+        rfc3339.write(w, &log.timestamp, false)?;
+        write!(w, "\",")?;
+      }
+    }
+
+    let is_label = |k: &&str| self.labels.contains(k);
+    for (k, v) in kv.iter().filter(|(k, _)| !is_label(k)) {
+      write!(w, "\"")?;
+      json_key(w, k, RESERVED)?;
+      write!(w, "\":\"")?;
+      write!(Escape::json(w), "{v}")?;
+      write!(w, "\",")?;
+    }
+
+    let mut labels = kv.iter().filter(|(k, _)| is_label(k)).peekable();
+    if labels.peek().is_some() {
+      write!(w, "\"logging.googleapis.com/labels\":{{")?;
+      for (i, (k, v)) in labels.enumerate() {
+        if i > 0 {
+          write!(w, ",")?;
+        }
+        write!(w, "\"")?;
+        write!(Escape::json(w), "{k}")?;
+        write!(w, "\":\"")?;
+        write!(Escape::json(w), "{v}")?;
+        write!(w, "\"")?;
+      }
+      write!(w, "}},")?;
+    }
+
+    // The line is an int64, which is a string in JSON
+    write!(w, "\"logging.googleapis.com/sourceLocation\":{{\"file\":\"")?;
+    write!(Escape::json(w), "{}", log.file)?;
+    write!(w, "\",\"line\":\"{}\",\"function\":\"", log.line)?;
+    write!(Escape::json(w), "{}", log.module)?;
+    writeln!(w, "\"}}}}")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+
+  #[test]
+  fn structured() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Warn,
+      kv: &[
+        ("tenant", format_args!("acme")),
+        ("latency_ms", format_args!("{}", 900)),
+        ("message", format_args!("Slow \"query\"")),
+      ],
+      module: "api::db",
+      file: "stackdriver.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Stackdriver {
+      labels: &["tenant"],
+      ..Default::default()
+    }
+    .format(&log, &mut w)
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [
+        r#"{"severity":"WARNING","message":"Slow \"query\"","time":"1970-01-01T00:00:00.000Z","#,
+        r#""latency_ms":"900","logging.googleapis.com/labels":{"tenant":"acme"},"#,
+        r#""logging.googleapis.com/sourceLocation":{"file":"stackdriver.rs","line":"10","function":"api::db"}}"#,
+        "\n"
+      ]
+      .concat()
+    );
+  }
+
+  #[test]
+  fn collisions() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("severity", format_args!("low")),
+        ("time", format_args!("{}", 5)),
+        ("message", format_args!("Collided")),
+      ],
+      module: "api",
+      file: "stackdriver.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Stackdriver::default().format(&log, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [
+        r#"{"severity":"INFO","message":"Collided","time":"1970-01-01T00:00:00.000Z","#,
+        r#""_severity":"low","_time":"5","#,
+        r#""logging.googleapis.com/sourceLocation":{"file":"stackdriver.rs","line":"10","function":"api"}}"#,
+        "\n"
+      ]
+      .concat()
+    );
+  }
+
+  #[test]
+  fn timestamps() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH
+        - time::Duration::milliseconds(1500),
+      level: LogLevel::Info,
+      kv: &[("message", format_args!("Early"))],
+      module: "api",
+      file: "stackdriver.rs",
+      line: 10,
+    };
+    let time = |timestamp| {
+      let mut w = vec![];
+      Stackdriver {
+        timestamp,
+        ..Default::default()
+      }
+      .format(&log, &mut w)
+      .unwrap();
+      let w = String::from_utf8(w).unwrap();
+      let start = w.find("Early\",").unwrap() + 7;
+      let end = w.find("\"logging").unwrap();
+      w[start..end].to_owned()
+    };
+
+    assert_eq!(
+      time(TimestampFormat::Unix(Precision::Millis)),
+      r#""timestampSeconds":-2,"timestampNanos":500000000,"#
+    );
+    assert_eq!(
+      time(TimestampFormat::Custom(time::macros::format_description!(
+        "[hour]:[minute]"
+      ))),
+      r#""time":"1969-12-31T23:59:58.500Z","#
+    );
+    assert_eq!(time(TimestampFormat::None), "");
+  }
+}
@@ -0,0 +1,364 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::{escape::Escape, Formatter},
+  log::{Log, LogLevel},
+  timestamp::{Precision, TimestampFormat},
+};
+
+/// The facility syslog messages are filed under, see
+/// [RFC 5424](https://datatracker.ietf.org/doc/html/rfc5424#section-6.2.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facility {
+  Kern = 0,
+  User = 1,
+  Mail = 2,
+  Daemon = 3,
+  Auth = 4,
+  Syslog = 5,
+  Lpr = 6,
+  News = 7,
+  Uucp = 8,
+  Cron = 9,
+  AuthPriv = 10,
+  Ftp = 11,
+  Local0 = 16,
+  Local1 = 17,
+  Local2 = 18,
+  Local3 = 19,
+  Local4 = 20,
+  Local5 = 21,
+  Local6 = 22,
+  Local7 = 23,
+}
+
+/// Header fields of syslog messages, used by
+/// [`LogFormat::Syslog5424`](crate::LogFormat::Syslog5424) and
+/// [`LogFormat::Syslog3164`](crate::LogFormat::Syslog3164).
+///
+/// RFC 5424 lines look like
+/// `<14>1 2023-06-14T09:41:05.123456Z host app 1234 - [lumbermill@32473 key="value"] Message`,
+/// with key-value pairs in a STRUCTURED-DATA element. RFC 3164 lines look like
+/// `<14>Jun 14 09:41:05 host app[1234]: Message key=value`.
+///
+/// By default, messages are filed under [`Facility::User`], with the hostname
+/// and application name of the current process.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{format::{Facility, Syslog}, LogFormat, Logger};
+///
+/// Logger::default().format(LogFormat::Syslog5424(
+///   Syslog::new().facility(Facility::Local0).app_name("billing"),
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syslog {
+  facility: Facility,
+  hostname: String,
+  app_name: String,
+  procid: String,
+  msgid: String,
+  sd_id: String,
+}
+
+impl Syslog {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn facility(mut self, facility: Facility) -> Self {
+    self.facility = facility;
+    self
+  }
+
+  pub fn hostname(mut self, hostname: &str) -> Self {
+    self.hostname = header(hostname, 255);
+    self
+  }
+
+  /// Also the TAG of RFC 3164 messages, which only keeps ASCII letters,
+  /// digits, `-`, `_` and `.`, and is `lumbermill` if none are left.
+  pub fn app_name(mut self, app_name: &str) -> Self {
+    self.app_name = header(app_name, 48);
+    self
+  }
+
+  /// Defaults to the ID of the current process.
+  pub fn procid(mut self, procid: &str) -> Self {
+    self.procid = header(procid, 128);
+    self
+  }
+
+  /// The MSGID of RFC 5424 messages. Empty by default.
+  pub fn msgid(mut self, msgid: &str) -> Self {
+    self.msgid = header(msgid, 32);
+    self
+  }
+
+  /// The SD-ID of the STRUCTURED-DATA element key-value pairs are put in.
+  /// Defaults to `lumbermill@32473`, 32473 being the enterprise number
+  /// reserved for documentation.
+  pub fn structured_data_id(mut self, id: &str) -> Self {
+    self.sd_id = id
+      .chars()
+      .map(|c| if is_sd_name(c) { c } else { '_' })
+      .take(32)
+      .collect();
+    self
+  }
+
+  /// The PRI part of messages logged at `level`.
+  pub(crate) fn pri(&self, level: LogLevel) -> u8 {
+    (self.facility as u8) * 8 + severity(level)
+  }
+
+  pub(crate) fn write_5424(
+    &self,
+    log: &Log,
+    w: &mut dyn io::Write,
+  ) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    write!(w, "<{}>1 ", self.pri(log.level))?;
+    TimestampFormat::Rfc3339(Precision::Micros).write(
+      w,
+      &log.timestamp,
+      false,
+    )?;
+    write!(
+      w,
+      " {} {} {} {} ",
+      self.hostname, self.app_name, self.procid, self.msgid
+    )?;
+
+    if kv.is_empty() {
+      w.write_all(b"-")?;
+    } else {
+      write!(w, "[{}", self.sd_id)?;
+      for (k, v) in kv {
+        w.write_all(b" ")?;
+        for c in k.chars().take(32) {
+          write!(w, "{}", if is_sd_name(c) { c } else { '_' })?;
+        }
+        w.write_all(b"=\"")?;
+        write!(Escape::backslashing(w, b"\"\\]"), "{v}")?;
+        w.write_all(b"\"")?;
+      }
+      w.write_all(b"]")?;
+    }
+
+    w.write_all(b" ")?;
+    write!(Escape::new(w), "{}", message.1)?;
+    writeln!(w)
+  }
+
+  pub(crate) fn write_3164(
+    &self,
+    log: &Log,
+    w: &mut dyn io::Write,
+  ) -> io::Result<()> {
+    const MONTHS: [&str; 12] = [
+      "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+      "Nov", "Dec",
+    ];
+
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    let t = &log.timestamp;
+    write!(
+      w,
+      "<{}>{} {:>2} {:0>2}:{:0>2}:{:0>2} {} ",
+      self.pri(log.level),
+      MONTHS[t.month() as usize - 1],
+      t.day(),
+      t.hour(),
+      t.minute(),
+      t.second(),
+      self.hostname,
+    )?;
+
+    // The TAG is limited to 32 alphanumeric characters, and may not be empty
+    let mut tag = self
+      .app_name
+      .chars()
+      .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+      .take(32)
+      .peekable();
+    if tag.peek().is_none() {
+      write!(w, "lumbermill")?;
+    }
+    for c in tag {
+      write!(w, "{c}")?;
+    }
+    write!(w, "[{}]: ", self.procid)?;
+
+    write!(Escape::new(w), "{}", message.1)?;
+    for (k, v) in kv {
+      write!(w, " {k}=")?;
+      write!(Escape::new(w), "{v}")?;
+    }
+    writeln!(w)
+  }
+}
+
+/// RFC 5424 syslog messages as a [`Formatter`], like
+/// [`LogFormat::Syslog5424`](crate::LogFormat::Syslog5424).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rfc5424(pub Syslog);
+
+impl Formatter for Rfc5424 {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    self.0.write_5424(log, w)
+  }
+}
+
+/// RFC 3164 syslog messages as a [`Formatter`], like
+/// [`LogFormat::Syslog3164`](crate::LogFormat::Syslog3164).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rfc3164(pub Syslog);
+
+impl Formatter for Rfc3164 {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    self.0.write_3164(log, w)
+  }
+}
+
+impl Default for Syslog {
+  fn default() -> Self {
+    let app_name = std::env::current_exe()
+      .ok()
+      .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+      .unwrap_or_default();
+
+    Self {
+      facility: Facility::User,
+      hostname: header(&hostname().unwrap_or_default(), 255),
+      app_name: header(&app_name, 48),
+      procid: std::process::id().to_string(),
+      msgid: String::from("-"),
+      sd_id: String::from("lumbermill@32473"),
+    }
+  }
+}
+
+/// Maps levels to syslog severities. There is no severity below `debug`, so
+/// `Trace` shares it.
+pub(crate) fn severity(level: LogLevel) -> u8 {
+  match level {
+    LogLevel::Trace | LogLevel::Debug => 7,
+    LogLevel::Info => 6,
+    LogLevel::Warn => 4,
+    LogLevel::Error => 3,
+    LogLevel::Fatal => 2,
+  }
+}
+
+/// The name of this machine, if it can be determined without a C library.
+pub(crate) fn hostname() -> Option<String> {
+  std::env::var("HOSTNAME")
+    .ok()
+    .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+    .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+    .map(|hostname| hostname.trim().to_owned())
+    .filter(|hostname| !hostname.is_empty())
+}
+
+/// Header fields may only contain printable ASCII characters, and are `-` when
+/// empty.
+fn header(value: &str, max: usize) -> String {
+  let value: String = value
+    .chars()
+    .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+    .take(max)
+    .collect();
+
+  if value.is_empty() {
+    String::from("-")
+  } else {
+    value
+  }
+}
+
+fn is_sd_name(c: char) -> bool {
+  c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"')
+}
+
+#[cfg(test)]
+mod tests {
+  use time::macros::datetime;
+
+  use super::*;
+
+  fn syslog() -> Syslog {
+    Syslog::new()
+      .facility(Facility::Local0)
+      .hostname("fra1")
+      .app_name("billing service")
+      .procid("1234")
+      .msgid("PAY")
+  }
+
+  fn log(kv: &[(&'static str, std::fmt::Arguments)], f: impl FnOnce(&Log)) {
+    f(&Log {
+      timestamp: datetime!(2023-06-04 09:41:05.123456789 UTC),
+      level: LogLevel::Warn,
+      kv,
+      module: "tests",
+      file: "syslog.rs",
+      line: 10,
+    })
+  }
+
+  #[test]
+  fn rfc5424() {
+    let mut w = vec![];
+    log(
+      &[
+        ("amount", format_args!("12.50")),
+        ("bad key", format_args!("a \"quoted\" ]value\\")),
+        ("message", format_args!("Payment\nfailed")),
+      ],
+      |log| Rfc5424(syslog()).format(log, &mut w).unwrap(),
+    );
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      "<132>1 2023-06-04T09:41:05.123456Z fra1 billing_service 1234 PAY [lumbermill@32473 amount=\"12.50\" bad_key=\"a \\\"quoted\\\" \\]value\\\\\"] Payment\\nfailed\n"
+    );
+
+    let mut w = vec![];
+    log(&[("message", format_args!("Hi"))], |log| {
+      Syslog::new().hostname("").write_5424(log, &mut w).unwrap()
+    });
+    assert!(String::from_utf8(w).unwrap().contains("Z - "));
+  }
+
+  #[test]
+  fn rfc3164() {
+    let mut w = vec![];
+    log(
+      &[
+        ("amount", format_args!("12.50")),
+        ("message", format_args!("Payment failed")),
+      ],
+      |log| Rfc3164(syslog()).format(log, &mut w).unwrap(),
+    );
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      "<132>Jun  4 09:41:05 fra1 billing_service[1234]: Payment failed amount=12.50\n"
+    );
+
+    let mut w = vec![];
+    log(&[("message", format_args!("Hi"))], |log| {
+      Syslog::new()
+        .app_name("@@")
+        .write_3164(log, &mut w)
+        .unwrap()
+    });
+    assert!(String::from_utf8(w).unwrap().contains(" lumbermill["));
+  }
+}
@@ -0,0 +1,431 @@
+use std::{
+  error::Error,
+  fmt,
+  io::{self, Write},
+};
+
+use crate::{
+  format::{
+    escape::{with_scratch, Escape},
+    pretty::{CYAN, DIM, FG_RESET, RED, RESET},
+    Formatter,
+  },
+  log::{Log, LogLevel},
+  timestamp::TimestampFormat,
+};
+
+/// A format described by a template string, like
+/// `"{ts} [{level:>5}] {module}: {message} {kv}"`.
+///
+/// Placeholders are replaced by parts of the log line:
+///
+/// - `{ts}`: the timestamp, see [`TimestampFormat`]
+/// - `{level}`: the level, like `info`
+/// - `{message}`
+/// - `{module}`, `{file}`, `{line}`, and `{src}` for `file:line`
+/// - `{kv.<key>}`: the value of a single key-value pair, or nothing if the
+///   line does not have it
+/// - `{kv}`: all key-value pairs as `key=value`, except for the message and
+///   those printed by a `{kv.<key>}` placeholder
+///
+/// Placeholders can be padded to a width, like Rust's format strings:
+/// `{level:>5}` right-aligns the level, `{module:-<20}` left-aligns the
+/// module and fills with dashes, `{message:^40}` centers the message. Use
+/// `{{` and `}}` for literal braces. A newline is added to the end of every
+/// line. Control characters in the message and values are escaped.
+///
+/// Templates are parsed once, when they are created.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{format::Template, LogFormat, Logger};
+///
+/// let template = Template::new("{ts} [{level:>5}] {module}: {message} {kv}")
+///   .unwrap()
+///   .colors(true);
+/// Logger::default().format(LogFormat::Template(template));
+///
+/// // Or, panicking if the template is invalid:
+/// Logger::default().template("{level:>5} {message}");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+  segments: Vec<Segment>,
+  colors: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Literal(String),
+  Field {
+    field: Field,
+    padding: Option<Padding>,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+  Timestamp,
+  Level,
+  Message,
+  Module,
+  File,
+  Line,
+  Src,
+  Kv,
+  Key(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Padding {
+  fill: char,
+  align: Align,
+  width: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+  Left,
+  Center,
+  Right,
+}
+
+/// Why a template could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+  /// Byte offset of the problem in the template
+  pub position: usize,
+  pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} (at position {})", self.message, self.position)
+  }
+}
+
+impl Error for TemplateError {}
+
+impl Template {
+  pub fn new(template: &str) -> Result<Self, TemplateError> {
+    let error = |position, message: &str| TemplateError {
+      position,
+      message: message.to_owned(),
+    };
+
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+      match c {
+        '{' if chars.next_if(|(_, c)| *c == '{').is_some() => literal.push('{'),
+        '}' if chars.next_if(|(_, c)| *c == '}').is_some() => literal.push('}'),
+        '}' => {
+          return Err(error(i, "Unmatched `}`, use `}}` for a literal one"))
+        }
+        '{' => {
+          let start = i + 1;
+          let end = loop {
+            match chars.next() {
+              Some((j, '}')) => break j,
+              Some(_) => {}
+              None => return Err(error(i, "Unclosed `{`")),
+            }
+          };
+
+          if !literal.is_empty() {
+            segments.push(Segment::Literal(std::mem::take(&mut literal)));
+          }
+
+          let placeholder = &template[start..end];
+          let (name, spec) = match placeholder.split_once(':') {
+            Some((name, spec)) => (name, Some(spec)),
+            None => (placeholder, None),
+          };
+
+          let field = match name {
+            "ts" => Field::Timestamp,
+            "level" => Field::Level,
+            "message" => Field::Message,
+            "module" => Field::Module,
+            "file" => Field::File,
+            "line" => Field::Line,
+            "src" => Field::Src,
+            "kv" => Field::Kv,
+            _ => match name.strip_prefix("kv.") {
+              Some(key) if !key.is_empty() => Field::Key(key.to_owned()),
+              _ => return Err(error(start, "Unknown placeholder")),
+            },
+          };
+
+          let padding =
+            match spec {
+              Some(spec) => Some(Padding::parse(spec).ok_or_else(|| {
+                error(start + name.len() + 1, "Invalid width")
+              })?),
+              None => None,
+            };
+
+          segments.push(Segment::Field { field, padding });
+        }
+        c => literal.push(c),
+      }
+    }
+
+    if !literal.is_empty() {
+      segments.push(Segment::Literal(literal));
+    }
+
+    Ok(Self {
+      segments,
+      colors: false,
+    })
+  }
+
+  /// Colorize the level, timestamp and other parts of lines like
+  /// [`Pretty`](crate::format::Pretty) does. Off by default.
+  pub fn colors(mut self, colors: bool) -> Self {
+    self.colors = colors;
+    self
+  }
+
+  pub(crate) fn write(
+    &self,
+    log: &Log,
+    w: &mut dyn io::Write,
+    timestamp: &TimestampFormat,
+  ) -> io::Result<()> {
+    for segment in &self.segments {
+      let (field, padding) = match segment {
+        Segment::Literal(literal) => {
+          w.write_all(literal.as_bytes())?;
+          continue;
+        }
+        Segment::Field { field, padding } => (field, padding),
+      };
+
+      let color = if self.colors { color(field, log) } else { None };
+      if let Some((start, _)) = color {
+        w.write_all(start.as_bytes())?;
+      }
+
+      match padding {
+        None => self.field(field, log, w, timestamp, self.colors)?,
+        // Padded fields are rendered first, so their width is known. Colors
+        // inside them would count towards their width.
+        Some(padding) => with_scratch(|scratch| {
+          self.field(field, log, scratch, timestamp, false)?;
+          padding.write(w, scratch)
+        })?,
+      }
+
+      if let Some((_, end)) = color {
+        w.write_all(end.as_bytes())?;
+      }
+    }
+
+    writeln!(w)
+  }
+
+  fn field(
+    &self,
+    field: &Field,
+    log: &Log,
+    w: &mut dyn io::Write,
+    timestamp: &TimestampFormat,
+    colors: bool,
+  ) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    match field {
+      Field::Timestamp => timestamp.write(w, &log.timestamp, false),
+      Field::Level => write!(w, "{:?}", log.level),
+      Field::Message => write!(Escape::new(w), "{}", message.1),
+      Field::Module => w.write_all(log.module.as_bytes()),
+      Field::File => w.write_all(log.file.as_bytes()),
+      Field::Line => write!(w, "{}", log.line),
+      Field::Src => write!(w, "{}:{}", log.file, log.line),
+      Field::Kv => {
+        let mut separator = "";
+        for (k, v) in kv.iter().filter(|(k, _)| !self.prints(k)) {
+          if colors {
+            write!(w, "{separator}{DIM}{k}={RESET}")?;
+          } else {
+            write!(w, "{separator}{k}=")?;
+          }
+          write!(Escape::new(w), "{v}")?;
+          separator = " ";
+        }
+        Ok(())
+      }
+      Field::Key(key) => match log.kv.iter().find(|(k, _)| k == key) {
+        Some((_, v)) => write!(Escape::new(w), "{v}"),
+        None => Ok(()),
+      },
+    }
+  }
+
+  /// Whether `key` has a placeholder of its own.
+  fn prints(&self, key: &str) -> bool {
+    self.segments.iter().any(|segment| {
+      matches!(segment, Segment::Field { field: Field::Key(k), .. } if k == key)
+    })
+  }
+}
+
+impl Formatter for Template {
+  fn format(&self, log: &Log, w: &mut dyn io::Write) -> io::Result<()> {
+    self.write(log, w, &TimestampFormat::default())
+  }
+}
+
+fn color(field: &Field, log: &Log) -> Option<(&'static str, &'static str)> {
+  match field {
+    Field::Timestamp
+    | Field::Module
+    | Field::File
+    | Field::Line
+    | Field::Src => Some((DIM, RESET)),
+    Field::Level => Some((log.level.ansi_fg(), FG_RESET)),
+    Field::Message
+      if log.level == LogLevel::Error || log.level == LogLevel::Fatal =>
+    {
+      Some((RED, FG_RESET))
+    }
+    Field::Key(_) => Some((CYAN, FG_RESET)),
+    Field::Message | Field::Kv => None,
+  }
+}
+
+impl Padding {
+  /// Parses `[[fill]align]width`, like Rust's format specs.
+  fn parse(spec: &str) -> Option<Self> {
+    let align = |c| match c {
+      '<' => Some(Align::Left),
+      '^' => Some(Align::Center),
+      '>' => Some(Align::Right),
+      _ => None,
+    };
+
+    let mut chars = spec.chars();
+    let (fill, align, width) = match (chars.next(), chars.next()) {
+      (Some(fill), Some(c)) if align(c).is_some() => {
+        (fill, align(c)?, chars.as_str())
+      }
+      (Some(c), _) if align(c).is_some() => (' ', align(c)?, &spec[1..]),
+      _ => (' ', Align::Left, spec),
+    };
+
+    Some(Self {
+      fill,
+      align,
+      width: width.parse().ok()?,
+    })
+  }
+
+  fn write(&self, w: &mut dyn io::Write, value: &[u8]) -> io::Result<()> {
+    let len = match std::str::from_utf8(value) {
+      Ok(value) => value.chars().count(),
+      Err(_) => value.len(),
+    };
+    let padding = self.width.saturating_sub(len);
+    let (before, after) = match self.align {
+      Align::Left => (0, padding),
+      Align::Center => (padding / 2, padding - padding / 2),
+      Align::Right => (padding, 0),
+    };
+
+    let mut fill = [0; 4];
+    let fill = self.fill.encode_utf8(&mut fill).as_bytes();
+    for _ in 0..before {
+      w.write_all(fill)?;
+    }
+    w.write_all(value)?;
+    for _ in 0..after {
+      w.write_all(fill)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+
+  fn render(template: &str, level: LogLevel, colors: bool) -> String {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level,
+      kv: &[
+        ("request_id", format_args!("42")),
+        ("path", format_args!("/health")),
+        ("message", format_args!("logmsg")),
+      ],
+      module: "tests",
+      file: "template.rs",
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Template::new(template)
+      .unwrap()
+      .colors(colors)
+      .format(&log, &mut w)
+      .unwrap();
+    String::from_utf8(w).unwrap()
+  }
+
+  #[test]
+  fn placeholders() {
+    assert_eq!(
+      render(
+        "{ts} [{level:>5}] {module}: {message} {kv}",
+        LogLevel::Info,
+        false
+      ),
+      "1970-01-01T00:00:00.000Z [ info] tests: logmsg request_id=42 path=/health\n"
+    );
+    assert_eq!(
+      render(
+        "{{{kv.request_id}}} {src} {file}@{line} {kv} {kv.missing}",
+        LogLevel::Info,
+        false
+      ),
+      "{42} template.rs:10 template.rs@10 path=/health \n"
+    );
+  }
+
+  #[test]
+  fn padding() {
+    assert_eq!(
+      render("|{level:<7}|{level:*^7}|{level:7}|", LogLevel::Warn, false),
+      "|warn   |*warn**|warn   |\n"
+    );
+    assert_eq!(render("{message:3}", LogLevel::Info, false), "logmsg\n");
+  }
+
+  #[test]
+  fn colors() {
+    assert_eq!(
+      render("{level:>6} {message} {kv.path}", LogLevel::Error, true),
+      "\x1b[38;5;1m error\x1b[39m \x1b[31mlogmsg\x1b[39m \x1b[36m/health\x1b[39m\n"
+    );
+  }
+
+  #[test]
+  fn errors() {
+    let error = |template| Template::new(template).unwrap_err().position;
+
+    assert_eq!(error("{level"), 0);
+    assert_eq!(error("level}"), 5);
+    assert_eq!(error("{lvl}"), 1);
+    assert_eq!(error("{kv.}"), 1);
+    assert_eq!(error("{level:>x}"), 7);
+  }
+}
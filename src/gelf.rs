@@ -0,0 +1,273 @@
+use std::{
+  borrow::Cow,
+  collections::hash_map::RandomState,
+  hash::{BuildHasher, Hasher},
+  io,
+  net::UdpSocket,
+  sync::atomic::{AtomicU64, Ordering},
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+  format::{escape::with_scratch, Formatter, Gelf},
+  log::Log,
+  sink::{connect_any, udp_socket, Sink},
+};
+
+/// Chunks start with these two bytes
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+/// Magic bytes, message ID, sequence number and sequence count
+const CHUNK_HEADER: usize = 12;
+/// Graylog discards messages with more chunks than this
+const MAX_CHUNKS: usize = 128;
+
+/// A [`Sink`] that sends [`Gelf`] messages to Graylog over UDP.
+///
+/// Lines are formatted as GELF no matter what format the
+/// [`Logger`](crate::Logger) uses. Messages that do not fit into a single
+/// datagram of [`GelfSink::chunk_size`] bytes are split into up to 128 chunks,
+/// and, with the `gzip` feature, can be compressed first (see
+/// [`GelfSink::gzip`]). Messages that are too large even then are dropped with
+/// an error.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{format::Gelf, GelfSink, Logger};
+///
+/// let graylog = GelfSink::udp("graylog.internal:12201").gelf(Gelf::new().host("api-1"));
+/// Logger::default().sink(graylog).init();
+/// ```
+#[derive(Debug)]
+pub struct GelfSink {
+  addr: String,
+  gelf: Gelf,
+  chunk_size: usize,
+  #[cfg(feature = "gzip")]
+  gzip: bool,
+  socket: Mutex<Option<UdpSocket>>,
+}
+
+impl GelfSink {
+  /// Send to Graylog's GELF UDP input at `addr` (like
+  /// `"graylog.internal:12201"`). The address is resolved again every time the
+  /// sink reconnects, and every address it resolves to is tried in turn.
+  pub fn udp(addr: &str) -> Self {
+    Self {
+      addr: addr.to_owned(),
+      gelf: Gelf::default(),
+      chunk_size: 1420,
+      #[cfg(feature = "gzip")]
+      gzip: false,
+      socket: Mutex::new(None),
+    }
+  }
+
+  /// Header fields of messages, like the host.
+  pub fn gelf(mut self, gelf: Gelf) -> Self {
+    self.gelf = gelf;
+    self
+  }
+
+  /// The largest datagram to send, chunks included. Defaults to 1420 bytes,
+  /// which fits through most networks. Raise it (up to 8192) on networks that
+  /// allow larger datagrams.
+  pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+    self.chunk_size = chunk_size.max(CHUNK_HEADER + 1);
+    self
+  }
+
+  /// Compress messages that do not fit into a single datagram with gzip before
+  /// chunking them. Disabled by default.
+  #[cfg(feature = "gzip")]
+  pub fn gzip(mut self, gzip: bool) -> Self {
+    self.gzip = gzip;
+    self
+  }
+
+  fn connect(&self) -> io::Result<UdpSocket> {
+    connect_any(&self.addr, udp_socket)
+  }
+
+  /// Splits `message` into datagrams, unless it fits into one.
+  fn datagrams<'a>(&self, message: &'a [u8]) -> io::Result<Vec<Cow<'a, [u8]>>> {
+    if message.len() <= self.chunk_size {
+      return Ok(vec![Cow::Borrowed(message)]);
+    }
+
+    let chunks = message.chunks(self.chunk_size - CHUNK_HEADER);
+    let count = chunks.len();
+    if count > MAX_CHUNKS {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Message needs {count} chunks, GELF allows {MAX_CHUNKS}"),
+      ));
+    }
+
+    let id = message_id();
+    Ok(
+      chunks
+        .enumerate()
+        .map(|(i, data)| {
+          let mut chunk = Vec::with_capacity(CHUNK_HEADER + data.len());
+          chunk.extend_from_slice(&CHUNK_MAGIC);
+          chunk.extend_from_slice(&id);
+          chunk.extend_from_slice(&[i as u8, count as u8]);
+          chunk.extend_from_slice(data);
+          Cow::Owned(chunk)
+        })
+        .collect(),
+    )
+  }
+
+  #[cfg(feature = "gzip")]
+  fn compress<'a>(&self, message: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+    if !self.gzip || message.len() <= self.chunk_size {
+      return Ok(Cow::Borrowed(message));
+    }
+
+    let mut gzip =
+      flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    io::Write::write_all(&mut gzip, message)?;
+    gzip.finish().map(Cow::Owned)
+  }
+
+  #[cfg(not(feature = "gzip"))]
+  fn compress<'a>(&self, message: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+    Ok(Cow::Borrowed(message))
+  }
+}
+
+/// A random ID for the chunks of a message.
+fn message_id() -> [u8; 8] {
+  static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+  let mut hasher = RandomState::new().build_hasher();
+  hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+  hasher.finish().to_le_bytes()
+}
+
+impl Sink for GelfSink {
+  fn log(&self, log: &Log, _: &[u8]) -> io::Result<()> {
+    with_scratch(|message| {
+      self.gelf.format(log, message)?;
+
+      // Messages are framed by datagrams, they do not need a newline
+      if message.last() == Some(&b'\n') {
+        message.pop();
+      }
+
+      let message = self.compress(message)?;
+      let datagrams = self.datagrams(&message)?;
+      let send = |socket: &UdpSocket| {
+        datagrams
+          .iter()
+          .try_for_each(|datagram| socket.send(datagram).map(|_| ()))
+      };
+
+      let mut socket = self.socket.lock();
+      if let Some(s) = socket.as_ref() {
+        if send(s).is_ok() {
+          return Ok(());
+        }
+      }
+
+      // Not connected yet, or the socket broke
+      *socket = None;
+      let s = self.connect()?;
+      send(&s)?;
+      *socket = Some(s);
+
+      Ok(())
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{info, Logger};
+
+  fn graylog() -> (UdpSocket, String) {
+    let graylog = UdpSocket::bind("127.0.0.1:0").unwrap();
+    graylog
+      .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+      .unwrap();
+    let addr = graylog.local_addr().unwrap().to_string();
+    (graylog, addr)
+  }
+
+  /// Receives a message, reassembling it if it was chunked
+  fn recv(graylog: &UdpSocket) -> Vec<u8> {
+    let mut buf = [0; 2048];
+    let len = graylog.recv(&mut buf).unwrap();
+    if buf[..2] != CHUNK_MAGIC {
+      return buf[..len].to_vec();
+    }
+
+    let count = buf[11] as usize;
+    let mut chunks = vec![None; count];
+    chunks[buf[10] as usize] = Some(buf[12..len].to_vec());
+    for _ in 1..count {
+      let mut chunk = [0; 2048];
+      let len = graylog.recv(&mut chunk).unwrap();
+      assert_eq!(chunk[2..10], buf[2..10]);
+      chunks[chunk[10] as usize] = Some(chunk[12..len].to_vec());
+    }
+    chunks.into_iter().flat_map(Option::unwrap).collect()
+  }
+
+  #[test]
+  fn single() {
+    let (graylog, addr) = graylog();
+    let sink = GelfSink::udp(&addr).gelf(Gelf::new().host("fra1"));
+    let logger = Logger::new().stdout(false).sink(sink);
+    info!(logger: &logger, "Short");
+
+    let message = String::from_utf8(recv(&graylog)).unwrap();
+    assert!(message.starts_with(
+      "{\"version\":\"1.1\",\"host\":\"fra1\",\"short_message\":\"Short\","
+    ));
+    assert!(message.ends_with('}'));
+  }
+
+  #[test]
+  fn chunked() {
+    let (graylog, addr) = graylog();
+    let sink = GelfSink::udp(&addr).chunk_size(512);
+    let logger = Logger::new().stdout(false).sink(sink);
+    let long = "x".repeat(2000);
+    info!(logger: &logger, "{long}");
+
+    let message = String::from_utf8(recv(&graylog)).unwrap();
+    assert!(message.contains(&format!("\"short_message\":\"{long}\"")));
+  }
+
+  #[test]
+  fn too_large() {
+    let sink = GelfSink::udp("127.0.0.1:12201").chunk_size(13);
+    assert!(sink.datagrams(&[0; 128]).is_ok());
+    assert!(sink.datagrams(&[0; 129]).is_err());
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn gzip() {
+    use std::io::Read;
+
+    let (graylog, addr) = graylog();
+    let sink = GelfSink::udp(&addr).chunk_size(512).gzip(true);
+    let logger = Logger::new().stdout(false).sink(sink);
+    let long = "x".repeat(2000);
+    info!(logger: &logger, "{long}");
+
+    // Compresses well enough to fit into a single datagram
+    let mut buf = [0; 2048];
+    let len = graylog.recv(&mut buf).unwrap();
+    let mut message = String::new();
+    flate2::read::GzDecoder::new(&buf[..len])
+      .read_to_string(&mut message)
+      .unwrap();
+    assert!(message.contains(&format!("\"short_message\":\"{long}\"")));
+  }
+}
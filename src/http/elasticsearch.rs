@@ -0,0 +1,93 @@
+use std::io::{self, Write};
+
+use crate::{format::escape::Escape, http::Encoder, log::LogFormat, Record};
+
+/// Encodes batches for the Elasticsearch (or OpenSearch)
+/// [bulk API](https://www.elastic.co/guide/en/elasticsearch/reference/current/docs-bulk.html),
+/// usually at `/_bulk`.
+///
+/// Every record becomes a `create` action for the configured index (which
+/// works for data streams too), followed by the record as a JSON document.
+/// Elasticsearch accepts bulk requests even when individual documents are
+/// rejected, so mapping errors only show up in its own logs.
+#[derive(Debug)]
+pub struct Elasticsearch {
+  index: String,
+  format: LogFormat,
+}
+
+impl Elasticsearch {
+  /// Add documents to `index`.
+  pub fn new(index: &str) -> Self {
+    Self {
+      index: index.to_owned(),
+      format: LogFormat::Json,
+    }
+  }
+
+  /// Write documents in `format` instead of [`LogFormat::Json`], like
+  /// [`LogFormat::Ecs`]. It must produce JSON objects.
+  pub fn format(mut self, format: LogFormat) -> Self {
+    self.format = format;
+    self
+  }
+}
+
+impl Encoder for Elasticsearch {
+  fn content_type(&self) -> &str {
+    "application/x-ndjson"
+  }
+
+  fn encode(
+    &self,
+    records: &[Record],
+    w: &mut dyn io::Write,
+  ) -> io::Result<()> {
+    let mut document = vec![];
+    for record in records {
+      write!(w, "{{\"create\":{{\"_index\":\"")?;
+      write!(Escape::json(w), "{}", self.index)?;
+      writeln!(w, "\"}}}}")?;
+
+      document.clear();
+      record.write(&mut document, &self.format)?;
+      if !document.ends_with(b"\n") {
+        document.push(b'\n');
+      }
+      w.write_all(&document)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+  use crate::LogLevel;
+
+  #[test]
+  fn bulk() {
+    let record = Record {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: vec![("message", "Indexed".to_owned())].into(),
+      module: "api".into(),
+      file: "elasticsearch.rs".into(),
+      line: 10,
+    };
+
+    let mut w = vec![];
+    Elasticsearch::new("logs-api")
+      .encode(&[record.clone(), record], &mut w)
+      .unwrap();
+    let action = "{\"create\":{\"_index\":\"logs-api\"}}\n";
+    let document = "{\"ts\":\"1970-01-01T00:00:00.000Z\",\"level\":\"info\",\"message\":\"Indexed\",\"mod\":\"api\",\"src\":\"elasticsearch.rs:10\"}\n";
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [action, document].concat().repeat(2)
+    );
+  }
+}
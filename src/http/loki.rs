@@ -0,0 +1,227 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::escape::Escape, http::Encoder, log::LogFormat,
+  timestamp::TimestampFormat, Record,
+};
+
+/// Labels, and the records that have them
+type Stream<'a> = (Vec<(String, String)>, Vec<&'a Record>);
+
+/// Encodes batches for the [Grafana Loki](https://grafana.com/oss/loki/) push
+/// API, usually at `/loki/api/v1/push`.
+///
+/// Records are grouped into streams by their labels: `level`, the labels set
+/// with [`Loki::label`], and the values of the keys chosen with
+/// [`Loki::labels_from`]. Labels named like one before them get a `_` in
+/// front, so a `level` label becomes `_level`. Lines are written in logfmt by default, without a
+/// timestamp, since Loki stores it alongside each line.
+#[derive(Debug)]
+pub struct Loki {
+  labels: Vec<(String, String)>,
+  keys: Vec<String>,
+  format: LogFormat,
+}
+
+impl Loki {
+  pub fn new() -> Self {
+    Self {
+      labels: vec![],
+      keys: vec![],
+      format: LogFormat::Logfmt,
+    }
+  }
+
+  /// Add the label `name="value"` to every stream.
+  pub fn label(mut self, name: &str, value: &str) -> Self {
+    self.labels.push((label_name(name), value.to_owned()));
+    self
+  }
+
+  /// Turn the values of these keys into labels. Keys that are not valid label
+  /// names are adjusted, `http.method` becomes `http_method`.
+  pub fn labels_from(mut self, keys: &[&str]) -> Self {
+    self.keys.extend(keys.iter().map(|k| k.to_string()));
+    self
+  }
+
+  /// Write lines in `format` instead of logfmt.
+  pub fn format(mut self, format: LogFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  fn labels(&self, record: &Record) -> Vec<(String, String)> {
+    let mut labels = vec![("level".to_owned(), format!("{:?}", record.level))];
+    let values = self.keys.iter().filter_map(|key| {
+      record
+        .get(key)
+        .map(|value| (label_name(key), value.to_owned()))
+    });
+    for (mut name, value) in self.labels.iter().cloned().chain(values) {
+      // Like fields in JSON formats, colliding labels get a `_` in front
+      while labels.iter().any(|(n, _)| *n == name) {
+        name.insert(0, '_');
+      }
+      labels.push((name, value));
+    }
+    labels
+  }
+}
+
+impl Default for Loki {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Label names may only contain ASCII letters, digits and underscores, and may
+/// not start with a digit.
+fn label_name(name: &str) -> String {
+  let mut label: String = name
+    .chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+    .collect();
+  if !label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+    label.insert(0, '_');
+  }
+  label
+}
+
+impl Encoder for Loki {
+  fn content_type(&self) -> &str {
+    "application/json"
+  }
+
+  fn encode(
+    &self,
+    records: &[Record],
+    w: &mut dyn io::Write,
+  ) -> io::Result<()> {
+    let mut streams: Vec<Stream> = vec![];
+    for record in records {
+      let labels = self.labels(record);
+      match streams.iter_mut().find(|(l, _)| *l == labels) {
+        Some((_, records)) => records.push(record),
+        None => streams.push((labels, vec![record])),
+      }
+    }
+
+    let mut line = vec![];
+    write!(w, "{{\"streams\":[")?;
+    for (i, (labels, records)) in streams.iter().enumerate() {
+      if i > 0 {
+        write!(w, ",")?;
+      }
+
+      write!(w, "{{\"stream\":{{")?;
+      for (j, (name, value)) in labels.iter().enumerate() {
+        if j > 0 {
+          write!(w, ",")?;
+        }
+        write!(w, "\"{name}\":\"")?;
+        write!(Escape::json(w), "{value}")?;
+        write!(w, "\"")?;
+      }
+
+      write!(w, "}},\"values\":[")?;
+      for (j, record) in records.iter().enumerate() {
+        if j > 0 {
+          write!(w, ",")?;
+        }
+
+        line.clear();
+        record.with_log(|log| {
+          log.write_with_timestamp(
+            &mut line,
+            &self.format,
+            &TimestampFormat::None,
+          )
+        })?;
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+
+        write!(w, "[\"{}\",\"", record.timestamp.unix_timestamp_nanos())?;
+        Escape::json(w).write_all(line)?;
+        write!(w, "\"]")?;
+      }
+      write!(w, "]}}")?;
+    }
+    write!(w, "]}}")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+  use crate::LogLevel;
+
+  #[test]
+  fn streams() {
+    let record = |level, tenant: &str, message: &str| Record {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level,
+      kv: vec![
+        ("tenant.id", tenant.to_owned()),
+        ("message", message.to_owned()),
+      ]
+      .into(),
+      module: "api".into(),
+      file: "loki.rs".into(),
+      line: 10,
+    };
+    let records = [
+      record(LogLevel::Info, "acme", "Started"),
+      record(LogLevel::Warn, "acme", "Slow \"query\""),
+      record(LogLevel::Info, "acme", "Stopped"),
+    ];
+
+    let loki = Loki::new().label("job", "api").labels_from(&["tenant.id"]);
+    let mut w = vec![];
+    loki.encode(&records, &mut w).unwrap();
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      concat!(
+        r#"{"streams":["#,
+        r#"{"stream":{"level":"info","job":"api","tenant_id":"acme"},"values":["#,
+        r#"["0","level=info message=Started tenant.id=acme mod=api src=loki.rs:10"],"#,
+        r#"["0","level=info message=Stopped tenant.id=acme mod=api src=loki.rs:10"]]},"#,
+        r#"{"stream":{"level":"warn","job":"api","tenant_id":"acme"},"values":["#,
+        r#"["0","level=warn message=\"Slow \\\"query\\\"\" tenant.id=acme mod=api src=loki.rs:10"]]}"#,
+        r#"]}"#
+      )
+    );
+    assert_eq!(label_name("9lives"), "_9lives");
+  }
+
+  #[test]
+  fn label_collisions() {
+    let record = Record {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: vec![
+        ("tenant.id", "acme".to_owned()),
+        ("tenant_id", "globex".to_owned()),
+        ("message", "Started".to_owned()),
+      ]
+      .into(),
+      module: "api".into(),
+      file: "loki.rs".into(),
+      line: 10,
+    };
+
+    let loki = Loki::new()
+      .label("level", "custom")
+      .labels_from(&["tenant.id", "tenant_id"]);
+    assert_eq!(
+      loki.labels(&record),
+      [
+        ("level".to_owned(), "info".to_owned()),
+        ("_level".to_owned(), "custom".to_owned()),
+        ("tenant_id".to_owned(), "acme".to_owned()),
+        ("_tenant_id".to_owned(), "globex".to_owned()),
+      ]
+    );
+  }
+}
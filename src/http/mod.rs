@@ -0,0 +1,540 @@
+//! Sending log lines to HTTP endpoints in batches.
+//!
+//! An [`HttpSink`] collects owned records and POSTs them in batches, encoded by
+//! an [`Encoder`]. Encoders for [Grafana Loki](Loki), the
+//! [Elasticsearch bulk API](Elasticsearch) and
+//! [OpenTelemetry collectors](Otlp) are built in.
+
+use std::{
+  fmt::Debug,
+  io::{self, BufRead, BufReader, Write},
+  net::TcpStream,
+  sync::atomic::{AtomicU64, Ordering},
+  thread,
+  time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+  log::Log,
+  sink::{connect_any, Sink},
+  Record,
+};
+
+mod elasticsearch;
+mod loki;
+mod otlp;
+
+pub use elasticsearch::Elasticsearch;
+pub use loki::Loki;
+pub use otlp::Otlp;
+
+/// Turns a batch of records into the body of a request.
+pub trait Encoder: Send + Sync {
+  /// The `Content-Type` of the bodies this encoder produces.
+  fn content_type(&self) -> &str;
+
+  fn encode(&self, records: &[Record], w: &mut dyn io::Write)
+    -> io::Result<()>;
+}
+
+impl Debug for dyn Encoder {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Encoder")
+  }
+}
+
+#[derive(Debug)]
+struct Pending {
+  records: Vec<Record>,
+  since: Option<Instant>,
+}
+
+/// A [`Sink`] that POSTs batches of records to an HTTP endpoint.
+///
+/// The sink batches synchronously: it has no thread or timer of its own, and
+/// only sends from the thread that logs, flushes or drops it. A batch is sent
+/// once it holds [`HttpSink::batch_size`] records, when a record comes in and
+/// the oldest one has waited for [`HttpSink::max_age`], whenever the sink is
+/// flushed, and when it is dropped. Failed requests (connection errors, `429`
+/// and `5xx` responses) are retried with exponential backoff; batches that
+/// still cannot be delivered are dropped and counted in [`HttpSink::dropped`].
+///
+/// Always wrap the sink in a [`NonBlocking`](crate::NonBlocking) sink.
+/// Requests then run on its worker thread, its bounded queue decides what
+/// happens to log lines while they are in flight, and the worker flushes the
+/// sink once lines have waited for its
+/// [`flush_interval`](crate::NonBlockingBuilder::flush_interval), so the last
+/// records of a quiet period are not held back. Used directly, the sink blocks
+/// the threads that log, and holds records until the next one comes in,
+/// [`Logger::flush`](crate::Logger::flush) or until it is dropped.
+///
+/// Only plain `http://` URLs are supported. Point the sink at a local agent or
+/// proxy to reach endpoints that require TLS.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{http::Loki, HttpSink, Logger, NonBlocking};
+///
+/// let loki = HttpSink::new(
+///   "http://127.0.0.1:3100/loki/api/v1/push",
+///   Loki::new().label("job", "api").labels_from(&["tenant"]),
+/// )
+/// .unwrap();
+/// let (loki, _guard) = NonBlocking::new(loki);
+/// Logger::default().sink(loki).init();
+/// ```
+#[derive(Debug)]
+pub struct HttpSink {
+  /// `host:port` to connect to
+  addr: String,
+  host: String,
+  path: String,
+  encoder: Box<dyn Encoder>,
+  headers: Vec<(String, String)>,
+  batch_size: usize,
+  max_age: Duration,
+  retries: u32,
+  backoff: Duration,
+  #[cfg(feature = "gzip")]
+  gzip: bool,
+  timeout: Duration,
+  pending: Mutex<Pending>,
+  dropped: AtomicU64,
+}
+
+impl HttpSink {
+  /// POST batches encoded by `encoder` to `url`, which must start with
+  /// `http://`.
+  pub fn new<E: Encoder + 'static>(url: &str, encoder: E) -> io::Result<Self> {
+    let url = url.strip_prefix("http://").ok_or_else(|| {
+      io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Only http:// URLs are supported, got {url}"),
+      )
+    })?;
+    let (host, path) = match url.find('/') {
+      Some(i) => url.split_at(i),
+      None => (url, "/"),
+    };
+    if host.is_empty() {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "URL has no host",
+      ));
+    }
+    // `[::1]` has colons too, but no port
+    let has_port = host.rsplit_once(':').is_some_and(|(_, port)| {
+      !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit())
+    });
+    let addr = if has_port {
+      host.to_owned()
+    } else {
+      format!("{host}:80")
+    };
+
+    Ok(Self {
+      addr,
+      host: host.to_owned(),
+      path: path.to_owned(),
+      encoder: Box::new(encoder),
+      headers: vec![],
+      batch_size: 1000,
+      max_age: Duration::from_secs(1),
+      retries: 3,
+      backoff: Duration::from_millis(100),
+      #[cfg(feature = "gzip")]
+      gzip: false,
+      timeout: Duration::from_secs(10),
+      pending: Mutex::new(Pending {
+        records: vec![],
+        since: None,
+      }),
+      dropped: AtomicU64::new(0),
+    })
+  }
+
+  /// Send `name: value` with every request, like an `Authorization` header.
+  pub fn header(mut self, name: &str, value: &str) -> Self {
+    self.headers.push((name.to_owned(), value.to_owned()));
+    self
+  }
+
+  /// Send a batch once it holds this many records. Defaults to 1000.
+  pub fn batch_size(mut self, batch_size: usize) -> Self {
+    self.batch_size = batch_size.max(1);
+    self
+  }
+
+  /// Send a batch when a record comes in and the oldest one has waited this
+  /// long. The sink does not check on its own, see [`HttpSink`] for how to
+  /// send batches of quiet periods on time. Defaults to 1 second.
+  pub fn max_age(mut self, max_age: Duration) -> Self {
+    self.max_age = max_age;
+    self
+  }
+
+  /// Retry failed requests this many times, waiting `backoff` before the first
+  /// retry and twice as long before every following one. Defaults to 3 retries
+  /// and 100ms.
+  pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+    self.retries = retries;
+    self.backoff = backoff;
+    self
+  }
+
+  /// Compress request bodies with gzip. Disabled by default.
+  #[cfg(feature = "gzip")]
+  pub fn gzip(mut self, gzip: bool) -> Self {
+    self.gzip = gzip;
+    self
+  }
+
+  /// How long connecting, sending a request and waiting for its response may
+  /// take. Defaults to 10 seconds.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Number of records dropped because they could not be delivered.
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  fn take(&self) -> Vec<Record> {
+    let mut pending = self.pending.lock();
+    pending.since = None;
+    std::mem::take(&mut pending.records)
+  }
+
+  /// Sends `batch`, retrying failed requests up to `retries` times.
+  fn send(&self, batch: &[Record], retries: u32) -> io::Result<()> {
+    if batch.is_empty() {
+      return Ok(());
+    }
+
+    let result = self.encode(batch).and_then(|body| {
+      let mut backoff = self.backoff;
+      let mut attempt = 0;
+      loop {
+        match self.post(&body) {
+          Ok(status) if (200..300).contains(&status) => return Ok(()),
+          Ok(status) if status != 429 && status < 500 => {
+            return Err(io::Error::new(
+              io::ErrorKind::InvalidData,
+              format!("Request failed with status {status}"),
+            ))
+          }
+          result if attempt >= retries => {
+            return result.and_then(|status| {
+              Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Request failed with status {status}"),
+              ))
+            })
+          }
+          _ => {}
+        }
+
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+      }
+    });
+
+    if result.is_err() {
+      self
+        .dropped
+        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+    }
+    result
+  }
+
+  fn encode(&self, batch: &[Record]) -> io::Result<Vec<u8>> {
+    let mut body = vec![];
+
+    #[cfg(feature = "gzip")]
+    if self.gzip {
+      let mut gzip =
+        flate2::write::GzEncoder::new(body, flate2::Compression::fast());
+      self.encoder.encode(batch, &mut gzip)?;
+      return gzip.finish();
+    }
+
+    self.encoder.encode(batch, &mut body)?;
+    Ok(body)
+  }
+
+  /// Sends one request, and returns the status code of its response.
+  fn post(&self, body: &[u8]) -> io::Result<u16> {
+    let mut stream = connect_any(&self.addr, |addr| {
+      TcpStream::connect_timeout(&addr, self.timeout)
+    })?;
+    stream.set_read_timeout(Some(self.timeout))?;
+    stream.set_write_timeout(Some(self.timeout))?;
+
+    let mut request = Vec::with_capacity(256 + body.len());
+    write!(request, "POST {} HTTP/1.1\r\n", self.path)?;
+    write!(request, "Host: {}\r\n", self.host)?;
+    write!(request, "Content-Type: {}\r\n", self.encoder.content_type())?;
+    write!(request, "Content-Length: {}\r\n", body.len())?;
+    #[cfg(feature = "gzip")]
+    if self.gzip {
+      write!(request, "Content-Encoding: gzip\r\n")?;
+    }
+    for (name, value) in &self.headers {
+      write!(request, "{name}: {value}\r\n")?;
+    }
+    write!(request, "Connection: close\r\n\r\n")?;
+    request.extend_from_slice(body);
+    stream.write_all(&request)?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    // HTTP/1.1 204 No Content
+    status
+      .split(' ')
+      .nth(1)
+      .and_then(|code| code.parse().ok())
+      .ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Invalid HTTP response")
+      })
+  }
+}
+
+impl Sink for HttpSink {
+  fn log(&self, log: &Log, _: &[u8]) -> io::Result<()> {
+    let batch = {
+      let mut pending = self.pending.lock();
+      pending.records.push(log.to_owned());
+      let since = *pending.since.get_or_insert_with(Instant::now);
+      if pending.records.len() < self.batch_size
+        && since.elapsed() < self.max_age
+      {
+        return Ok(());
+      }
+      pending.since = None;
+      std::mem::take(&mut pending.records)
+    };
+
+    self.send(&batch, self.retries)
+  }
+
+  fn flush(&self) -> io::Result<()> {
+    let batch = self.take();
+    self.send(&batch, self.retries)
+  }
+}
+
+impl Drop for HttpSink {
+  fn drop(&mut self) {
+    // Dropping should not hold up shutdown for the whole backoff
+    let batch = self.take();
+    _ = self.send(&batch, 0);
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use std::{
+    io::Read,
+    net::TcpListener,
+    thread::{self, JoinHandle},
+  };
+
+  use super::*;
+  use crate::{info, Logger};
+
+  pub(crate) struct Request {
+    pub(crate) head: String,
+    pub(crate) body: Vec<u8>,
+  }
+
+  /// Answers requests with `statuses`, one per connection, and returns what
+  /// was requested.
+  pub(crate) fn server(statuses: &[u16]) -> (String, JoinHandle<Vec<Request>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/push", listener.local_addr().unwrap());
+    let statuses = statuses.to_vec();
+
+    let server = thread::spawn(move || {
+      statuses
+        .into_iter()
+        .map(|status| {
+          let (stream, _) = listener.accept().unwrap();
+          let mut reader = BufReader::new(stream);
+
+          let mut head = String::new();
+          while !head.ends_with("\r\n\r\n") {
+            reader.read_line(&mut head).unwrap();
+          }
+          let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+          let mut body = vec![0; length];
+          reader.read_exact(&mut body).unwrap();
+
+          write!(reader.get_mut(), "HTTP/1.1 {status} Status\r\n\r\n").unwrap();
+          Request { head, body }
+        })
+        .collect()
+    });
+
+    (url, server)
+  }
+
+  /// One line of text per record
+  struct Lines;
+
+  impl Encoder for Lines {
+    fn content_type(&self) -> &str {
+      "text/plain"
+    }
+
+    fn encode(
+      &self,
+      records: &[Record],
+      w: &mut dyn io::Write,
+    ) -> io::Result<()> {
+      records
+        .iter()
+        .try_for_each(|record| writeln!(w, "{}", record.message()))
+    }
+  }
+
+  #[test]
+  fn batches() {
+    let (url, server) = server(&[204, 200]);
+    let sink = HttpSink::new(&url, Lines)
+      .unwrap()
+      .header("Authorization", "Bearer hunter2")
+      .batch_size(2);
+    let logger = Logger::new().stdout(false).sink(sink);
+
+    for i in 0..3 {
+      info!(logger: &logger, "Line {i}");
+    }
+    drop(logger);
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].head.starts_with("POST /push HTTP/1.1\r\n"));
+    assert!(requests[0]
+      .head
+      .contains("\r\nAuthorization: Bearer hunter2\r\n"));
+    assert!(requests[0]
+      .head
+      .contains("\r\nContent-Type: text/plain\r\n"));
+    assert_eq!(requests[0].body, b"Line 0\nLine 1\n");
+    assert_eq!(requests[1].body, b"Line 2\n");
+  }
+
+  #[test]
+  fn retries() {
+    let (url, server) = server(&[503, 429, 204, 400]);
+    let sink = HttpSink::new(&url, Lines)
+      .unwrap()
+      .batch_size(1)
+      .retries(2, Duration::from_millis(1));
+
+    let record = Record {
+      timestamp: time::OffsetDateTime::UNIX_EPOCH,
+      level: crate::LogLevel::Info,
+      kv: vec![("message", "Retried".to_owned())].into(),
+      module: "http".into(),
+      file: "mod.rs".into(),
+      line: 10,
+    };
+    record.with_log(|log| sink.log(log, b"")).unwrap();
+    assert!(record.with_log(|log| sink.log(log, b"")).is_err());
+    assert_eq!(sink.dropped(), 1);
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[2].body, b"Retried\n");
+  }
+
+  #[test]
+  fn drops_after_one_attempt() {
+    let (url, server) = server(&[503]);
+    let sink = HttpSink::new(&url, Lines)
+      .unwrap()
+      .retries(3, Duration::from_secs(60));
+    let logger = Logger::new().stdout(false).sink(sink);
+    info!(logger: &logger, "Lost");
+
+    let start = Instant::now();
+    drop(logger);
+    assert!(start.elapsed() < Duration::from_secs(30));
+    assert_eq!(server.join().unwrap().len(), 1);
+  }
+
+  #[test]
+  fn max_age() {
+    let (url, server) = server(&[204, 204]);
+    let sink = HttpSink::new(&url, Lines)
+      .unwrap()
+      .max_age(Duration::from_millis(20));
+
+    let log = |message: &str| {
+      Record {
+        timestamp: time::OffsetDateTime::UNIX_EPOCH,
+        level: crate::LogLevel::Info,
+        kv: vec![("message", message.to_owned())].into(),
+        module: "http".into(),
+        file: "mod.rs".into(),
+        line: 10,
+      }
+      .with_log(|log| sink.log(log, b""))
+      .unwrap()
+    };
+    log("Early");
+    log("Late");
+    assert_eq!(sink.pending.lock().records.len(), 2);
+
+    thread::sleep(Duration::from_millis(30));
+    log("Later");
+    assert!(sink.pending.lock().records.is_empty());
+
+    // Flushing sends whatever is pending, however long it waited
+    log("Flushed");
+    sink.flush().unwrap();
+    assert!(sink.pending.lock().records.is_empty());
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].body, b"Early\nLate\nLater\n");
+    assert_eq!(requests[1].body, b"Flushed\n");
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn gzip() {
+    let (url, server) = server(&[204]);
+    let sink = HttpSink::new(&url, Lines).unwrap().gzip(true);
+    let logger = Logger::new().stdout(false).sink(sink);
+    info!(logger: &logger, "Compressed");
+    drop(logger);
+
+    let request = &server.join().unwrap()[0];
+    assert!(request.head.contains("\r\nContent-Encoding: gzip\r\n"));
+    let mut body = String::new();
+    flate2::read::GzDecoder::new(&request.body[..])
+      .read_to_string(&mut body)
+      .unwrap();
+    assert_eq!(body, "Compressed\n");
+  }
+
+  #[test]
+  fn urls() {
+    let sink = HttpSink::new("http://localhost:9200", Lines).unwrap();
+    assert_eq!((&*sink.addr, &*sink.path), ("localhost:9200", "/"));
+    let sink = HttpSink::new("http://[::1]/_bulk", Lines).unwrap();
+    assert_eq!((&*sink.addr, &*sink.path), ("[::1]:80", "/_bulk"));
+    assert!(HttpSink::new("https://localhost", Lines).is_err());
+    assert!(HttpSink::new("http:///path", Lines).is_err());
+  }
+}
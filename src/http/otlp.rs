@@ -0,0 +1,468 @@
+use std::io::{self, Write};
+
+use crate::{
+  format::escape::Escape,
+  http::Encoder,
+  log::LogLevel,
+  protobuf::{
+    write_fixed64, write_int, write_message, write_string, write_uint,
+  },
+  Fields, Record,
+};
+
+/// Encodes batches as [OpenTelemetry](https://opentelemetry.io/docs/specs/otlp/)
+/// log export requests, for the OTLP/HTTP endpoint of a collector (usually
+/// port 4318, at `/v1/logs`).
+///
+/// Every record becomes a `LogRecord`, with its level as the severity and its
+/// message as the body. Key-value pairs become attributes, along with the
+/// module, file and line as `code.namespace`, `code.filepath` and
+/// `code.lineno`. Fields set with [`Otlp::resource`] describe the service that
+/// logs, like its `service.name`, which defaults to the name of the
+/// executable. Fields bound to child loggers or a task's context are record
+/// attributes, not resource attributes.
+///
+/// Timestamps before 1970 cannot be represented, and are exported as 0, which
+/// collectors treat as unknown.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{fields, http::Otlp, HttpSink};
+///
+/// let service = "billing";
+/// let otlp = HttpSink::new(
+///   "http://127.0.0.1:4318/v1/logs",
+///   Otlp::protobuf().resource(fields!(service.name = service)),
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Otlp {
+  protobuf: bool,
+  resource: Fields,
+  /// The `service.name` unless the resource has one
+  service_name: String,
+}
+
+/// A typed attribute value
+enum Value<'a> {
+  String(&'a str),
+  Int(i64),
+}
+
+impl Otlp {
+  /// Encode requests as JSON (`application/json`).
+  pub fn json() -> Self {
+    let service_name = std::env::current_exe()
+      .ok()
+      .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+      .unwrap_or_default();
+
+    Self {
+      protobuf: false,
+      resource: Fields::new(),
+      service_name,
+    }
+  }
+
+  /// Encode requests as protobuf (`application/x-protobuf`), which collectors
+  /// decode more efficiently.
+  pub fn protobuf() -> Self {
+    Self {
+      protobuf: true,
+      ..Self::json()
+    }
+  }
+
+  /// Attach `fields` to the resource every record is exported with.
+  pub fn resource(mut self, fields: Fields) -> Self {
+    self.resource.extend(fields);
+    self
+  }
+
+  /// The resource's attributes, with a default `service.name`.
+  fn resource_attributes(&self) -> impl Iterator<Item = (&'static str, &str)> {
+    let default = !self.service_name.is_empty()
+      && !self.resource.iter().any(|(k, _)| k == "service.name");
+    default
+      .then_some(("service.name", self.service_name.as_str()))
+      .into_iter()
+      .chain(self.resource.iter())
+  }
+
+  fn encode_protobuf(&self, records: &[Record], w: &mut Vec<u8>) {
+    // ExportLogsServiceRequest.resource_logs
+    write_message(w, 1, |w| {
+      // ResourceLogs.resource
+      write_message(w, 1, |w| {
+        for (k, v) in self.resource_attributes() {
+          write_attribute(w, 1, k, Value::String(v));
+        }
+      });
+
+      // ResourceLogs.scope_logs
+      write_message(w, 2, |w| {
+        write_message(w, 1, |w| {
+          write_string(w, 1, env!("CARGO_PKG_NAME"));
+          write_string(w, 2, env!("CARGO_PKG_VERSION"));
+        });
+
+        for record in records {
+          // ScopeLogs.log_records
+          write_message(w, 2, |w| {
+            write_fixed64(w, 1, time_unix_nano(record));
+            write_uint(w, 2, severity_number(record.level));
+            write_string(w, 3, severity_text(record.level));
+            write_message(w, 5, |w| write_string(w, 1, record.message()));
+            for (k, v) in attributes(record) {
+              write_attribute(w, 6, k, v);
+            }
+          });
+        }
+      });
+    });
+  }
+
+  fn encode_json(
+    &self,
+    records: &[Record],
+    w: &mut dyn io::Write,
+  ) -> io::Result<()> {
+    write!(w, "{{\"resourceLogs\":[{{\"resource\":{{\"attributes\":[")?;
+    for (i, (k, v)) in self.resource_attributes().enumerate() {
+      if i > 0 {
+        write!(w, ",")?;
+      }
+      write_json_attribute(w, k, Value::String(v))?;
+    }
+
+    write!(
+      w,
+      "]}},\"scopeLogs\":[{{\"scope\":{{\"name\":\"{}\",\"version\":\"{}\"}},\"logRecords\":[",
+      env!("CARGO_PKG_NAME"),
+      env!("CARGO_PKG_VERSION")
+    )?;
+    for (i, record) in records.iter().enumerate() {
+      if i > 0 {
+        write!(w, ",")?;
+      }
+
+      // 64-bit integers are strings in JSON
+      write!(
+        w,
+        "{{\"timeUnixNano\":\"{}\",\"severityNumber\":{},\"severityText\":\"{}\",\"body\":{{\"stringValue\":\"",
+        time_unix_nano(record),
+        severity_number(record.level),
+        severity_text(record.level)
+      )?;
+      write!(Escape::json(w), "{}", record.message())?;
+      write!(w, "\"}},\"attributes\":[")?;
+      for (j, (k, v)) in attributes(record).enumerate() {
+        if j > 0 {
+          write!(w, ",")?;
+        }
+        write_json_attribute(w, k, v)?;
+      }
+      write!(w, "]}}")?;
+    }
+
+    write!(w, "]}}]}}]}}")
+  }
+}
+
+impl Encoder for Otlp {
+  fn content_type(&self) -> &str {
+    if self.protobuf {
+      "application/x-protobuf"
+    } else {
+      "application/json"
+    }
+  }
+
+  fn encode(
+    &self,
+    records: &[Record],
+    w: &mut dyn io::Write,
+  ) -> io::Result<()> {
+    if self.protobuf {
+      let mut request = vec![];
+      self.encode_protobuf(records, &mut request);
+      w.write_all(&request)
+    } else {
+      self.encode_json(records, w)
+    }
+  }
+}
+
+/// Nanoseconds since the Unix epoch, or 0 (unknown) before it.
+fn time_unix_nano(record: &Record) -> u64 {
+  record
+    .timestamp
+    .unix_timestamp_nanos()
+    .try_into()
+    .unwrap_or(0)
+}
+
+fn severity_number(level: LogLevel) -> u64 {
+  match level {
+    LogLevel::Trace => 1,
+    LogLevel::Debug => 5,
+    LogLevel::Info => 9,
+    LogLevel::Warn => 13,
+    LogLevel::Error => 17,
+    LogLevel::Fatal => 21,
+  }
+}
+
+fn severity_text(level: LogLevel) -> &'static str {
+  match level {
+    LogLevel::Trace => "TRACE",
+    LogLevel::Debug => "DEBUG",
+    LogLevel::Info => "INFO",
+    LogLevel::Warn => "WARN",
+    LogLevel::Error => "ERROR",
+    LogLevel::Fatal => "FATAL",
+  }
+}
+
+/// The key-value pairs of `record` other than its message, followed by where
+/// it was logged from.
+fn attributes(record: &Record) -> impl Iterator<Item = (&str, Value<'_>)> {
+  record
+    .kv
+    .iter()
+    .filter(|(k, _)| *k != "message")
+    .map(|(k, v)| (k, Value::String(v)))
+    .chain([
+      ("code.namespace", Value::String(&record.module)),
+      ("code.filepath", Value::String(&record.file)),
+      ("code.lineno", Value::Int(record.line.into())),
+    ])
+}
+
+/// Writes a `KeyValue` as `field`.
+fn write_attribute(w: &mut Vec<u8>, field: u32, key: &str, value: Value) {
+  write_message(w, field, |w| {
+    write_string(w, 1, key);
+    // AnyValue
+    write_message(w, 2, |w| match value {
+      Value::String(s) => write_string(w, 1, s),
+      Value::Int(n) => write_int(w, 3, n),
+    });
+  });
+}
+
+fn write_json_attribute(
+  w: &mut dyn io::Write,
+  key: &str,
+  value: Value,
+) -> io::Result<()> {
+  write!(w, "{{\"key\":\"")?;
+  write!(Escape::json(w), "{key}")?;
+  match value {
+    Value::String(s) => {
+      write!(w, "\",\"value\":{{\"stringValue\":\"")?;
+      write!(Escape::json(w), "{s}")?;
+      write!(w, "\"}}}}")
+    }
+    Value::Int(n) => write!(w, "\",\"value\":{{\"intValue\":\"{n}\"}}}}"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use time::OffsetDateTime;
+
+  use super::*;
+  use crate::{fields, http::tests::server, info, HttpSink, Logger};
+
+  fn record() -> Record {
+    Record {
+      timestamp: OffsetDateTime::UNIX_EPOCH + time::Duration::seconds(1),
+      level: LogLevel::Warn,
+      kv: vec![
+        ("retries", "3".to_owned()),
+        ("message", "Slow \"query\"".to_owned()),
+      ]
+      .into(),
+      module: "billing::db".into(),
+      file: "otlp.rs".into(),
+      line: 10,
+    }
+  }
+
+  /// A decoded protobuf field: varints and fixed64s as numbers, everything
+  /// else as bytes.
+  #[derive(Debug, PartialEq)]
+  enum Field {
+    Int(u64),
+    Bytes(Vec<u8>),
+  }
+
+  impl Field {
+    fn bytes(&self) -> Vec<u8> {
+      match self {
+        Field::Bytes(bytes) => bytes.clone(),
+        Field::Int(_) => panic!("Expected bytes"),
+      }
+    }
+  }
+
+  fn decode(mut buf: &[u8]) -> Vec<(u64, Field)> {
+    fn varint(buf: &mut &[u8]) -> u64 {
+      let mut n = 0;
+      for shift in (0..).step_by(7) {
+        let b = buf[0];
+        *buf = &buf[1..];
+        n |= u64::from(b & 0x7f) << shift;
+        if b < 0x80 {
+          break;
+        }
+      }
+      n
+    }
+
+    let mut fields = vec![];
+    while !buf.is_empty() {
+      let tag = varint(&mut buf);
+      let field = match tag & 7 {
+        0 => Field::Int(varint(&mut buf)),
+        1 => {
+          let (n, rest) = buf.split_at(8);
+          buf = rest;
+          Field::Int(u64::from_le_bytes(n.try_into().unwrap()))
+        }
+        2 => {
+          let len = varint(&mut buf) as usize;
+          let (bytes, rest) = buf.split_at(len);
+          buf = rest;
+          Field::Bytes(bytes.to_vec())
+        }
+        wire_type => panic!("Unexpected wire type {wire_type}"),
+      };
+      fields.push((tag >> 3, field));
+    }
+    fields
+  }
+
+  /// The bytes of the only field numbered `n`
+  fn bytes(fields: &[(u64, Field)], n: u64) -> Vec<u8> {
+    let mut matching = fields.iter().filter(|(f, _)| *f == n);
+    match (matching.next(), matching.next()) {
+      (Some((_, Field::Bytes(bytes))), None) => bytes.clone(),
+      _ => panic!("Expected a single field {n} in {fields:?}"),
+    }
+  }
+
+  #[test]
+  fn json() {
+    let otlp = Otlp::json().resource(fields!(service.name = "billing"));
+    let mut w = vec![];
+    otlp.encode(&[record()], &mut w).unwrap();
+
+    assert_eq!(
+      String::from_utf8(w).unwrap(),
+      [
+        r#"{"resourceLogs":[{"resource":{"attributes":[{"key":"service.name","value":{"stringValue":"billing"}}]},"#,
+        r#""scopeLogs":[{"scope":{"name":"lumbermill","version":""#,
+        env!("CARGO_PKG_VERSION"),
+        r#""},"logRecords":[{"timeUnixNano":"1000000000","severityNumber":13,"severityText":"WARN","#,
+        r#""body":{"stringValue":"Slow \"query\""},"attributes":["#,
+        r#"{"key":"retries","value":{"stringValue":"3"}},"#,
+        r#"{"key":"code.namespace","value":{"stringValue":"billing::db"}},"#,
+        r#"{"key":"code.filepath","value":{"stringValue":"otlp.rs"}},"#,
+        r#"{"key":"code.lineno","value":{"intValue":"10"}}]}]}]}]}"#,
+      ]
+      .concat()
+    );
+  }
+
+  #[test]
+  fn protobuf() {
+    let otlp = Otlp::protobuf().resource(fields!(service.name = "billing"));
+    let mut w = vec![];
+    otlp.encode(&[record(), record()], &mut w).unwrap();
+
+    let resource_logs = decode(&bytes(&decode(&w), 1));
+    let resource = decode(&bytes(&resource_logs, 1));
+    let attribute = decode(&bytes(&resource, 1));
+    assert_eq!(bytes(&attribute, 1), b"service.name");
+    assert_eq!(
+      decode(&bytes(&attribute, 2)),
+      [(1, Field::Bytes(b"billing".to_vec()))]
+    );
+
+    let scope_logs = decode(&bytes(&resource_logs, 2));
+    assert_eq!(bytes(&decode(&bytes(&scope_logs, 1)), 1), b"lumbermill");
+    let Field::Bytes(log_record) = &scope_logs[2].1 else {
+      panic!()
+    };
+    assert_eq!(scope_logs.len(), 3);
+
+    let log_record = decode(log_record);
+    assert_eq!(log_record[0], (1, Field::Int(1_000_000_000)));
+    assert_eq!(log_record[1], (2, Field::Int(13)));
+    assert_eq!(log_record[2], (3, Field::Bytes(b"WARN".to_vec())));
+    assert_eq!(
+      decode(&log_record[3].1.bytes()),
+      [(1, Field::Bytes(b"Slow \"query\"".to_vec()))]
+    );
+
+    let attributes: Vec<_> = log_record[4..]
+      .iter()
+      .map(|(n, attribute)| {
+        assert_eq!(*n, 6);
+        let attribute = decode(&attribute.bytes());
+        let key = String::from_utf8(bytes(&attribute, 1)).unwrap();
+        (key, decode(&bytes(&attribute, 2)).remove(0))
+      })
+      .collect();
+    assert_eq!(
+      attributes,
+      [
+        ("retries".into(), (1, Field::Bytes(b"3".to_vec()))),
+        (
+          "code.namespace".into(),
+          (1, Field::Bytes(b"billing::db".to_vec()))
+        ),
+        (
+          "code.filepath".into(),
+          (1, Field::Bytes(b"otlp.rs".to_vec()))
+        ),
+        ("code.lineno".into(), (3, Field::Int(10))),
+      ]
+    );
+  }
+
+  #[test]
+  fn defaults() {
+    let mut record = record();
+    record.timestamp = OffsetDateTime::UNIX_EPOCH - time::Duration::seconds(1);
+    let mut w = vec![];
+    Otlp::json().encode(&[record], &mut w).unwrap();
+    let w = String::from_utf8(w).unwrap();
+
+    let exe = std::env::current_exe().unwrap();
+    let exe = exe.file_name().unwrap().to_str().unwrap();
+    assert!(w.contains(&format!(
+      r#""attributes":[{{"key":"service.name","value":{{"stringValue":"{exe}"}}}}]"#
+    )));
+    assert!(w.contains(r#""timeUnixNano":"0""#));
+  }
+
+  #[test]
+  fn export() {
+    let (url, server) = server(&[200]);
+    let url = url.replace("/push", "/v1/logs");
+    let sink = HttpSink::new(&url, Otlp::protobuf()).unwrap();
+    let logger = Logger::new().stdout(false).sink(sink);
+    info!(logger: &logger, "Exported");
+    drop(logger);
+
+    let request = &server.join().unwrap()[0];
+    assert!(request.head.starts_with("POST /v1/logs HTTP/1.1\r\n"));
+    assert!(request
+      .head
+      .contains("\r\nContent-Type: application/x-protobuf\r\n"));
+  }
+}
@@ -0,0 +1,313 @@
+use std::{
+  io::{self, Write},
+  os::unix::net::UnixDatagram,
+  path::PathBuf,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+  format::{escape::with_scratch, syslog::severity},
+  log::Log,
+  sink::Sink,
+};
+
+/// A [`Sink`] that writes to the systemd journal, using its
+/// [native protocol](https://systemd.io/JOURNAL_NATIVE_PROTOCOL/).
+///
+/// Every line becomes a journal entry with these fields:
+///
+/// - `MESSAGE`
+/// - `PRIORITY`, the syslog severity of the level
+/// - `SYSLOG_IDENTIFIER`, the name of the executable by default
+/// - `CODE_MODULE`, `CODE_FILE` and `CODE_LINE`
+/// - One field per key-value pair, with the key upper-cased and characters
+///   other than letters, digits and `_` replaced by `_` (`request.id` becomes
+///   `REQUEST_ID`). Keys that would overwrite one of the fields above or
+///   another `SYSLOG_` field or `ERRNO`, like `priority` or `code.line`, are
+///   prefixed with `USER_`.
+///
+/// The journal stamps entries itself, so the [`Logger`](crate::Logger)'s
+/// format and timestamps are not used. Entries that do not fit in a single
+/// datagram are sent again with their values truncated to fit, ending in `…`.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{JournaldSink, Logger};
+///
+/// Logger::default().stdout(false).sink(JournaldSink::new()).init();
+/// ```
+#[derive(Debug)]
+pub struct JournaldSink {
+  path: PathBuf,
+  identifier: String,
+  socket: Mutex<Option<UnixDatagram>>,
+}
+
+impl JournaldSink {
+  pub fn new() -> Self {
+    Self::with_path("/run/systemd/journal/socket")
+  }
+
+  /// Send entries to the socket at `path` instead of journald's.
+  pub fn with_path<P: Into<PathBuf>>(path: P) -> Self {
+    let identifier = std::env::current_exe()
+      .ok()
+      .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+      .unwrap_or_default();
+
+    Self {
+      path: path.into(),
+      identifier,
+      socket: Mutex::new(None),
+    }
+  }
+
+  /// The `SYSLOG_IDENTIFIER` of entries. Defaults to the name of the
+  /// executable.
+  pub fn identifier(mut self, identifier: &str) -> Self {
+    self.identifier = identifier.to_owned();
+    self
+  }
+
+  /// Writes the entry for `log`, with values longer than `limit` bytes
+  /// truncated.
+  fn entry(&self, log: &Log, w: &mut Vec<u8>, limit: usize) -> io::Result<()> {
+    // Because of the way our macros are set up, the KV list is ordered, which means
+    // that the message will always be the last element
+    let (message, kv) = log.kv.split_last().expect("A log message is required");
+
+    field(w, "MESSAGE", limit, |w| write!(w, "{}", message.1))?;
+    writeln!(w, "PRIORITY={}", severity(log.level))?;
+    if !self.identifier.is_empty() {
+      field(w, "SYSLOG_IDENTIFIER", limit, |w| {
+        w.write_all(self.identifier.as_bytes())
+      })?;
+    }
+    field(w, "CODE_MODULE", limit, |w| {
+      w.write_all(log.module.as_bytes())
+    })?;
+    field(w, "CODE_FILE", limit, |w| w.write_all(log.file.as_bytes()))?;
+    writeln!(w, "CODE_LINE={}", log.line)?;
+
+    for (k, v) in kv {
+      // Field names must start with a letter. Ones starting with `_` are
+      // reserved for fields journald adds itself.
+      let k = k.trim_start_matches(|c: char| !c.is_ascii_alphabetic());
+      if k.is_empty() {
+        continue;
+      }
+
+      let start = w.len();
+      w.extend(k.chars().take(64).map(|c| {
+        if c.is_ascii_alphanumeric() {
+          c.to_ascii_uppercase() as u8
+        } else {
+          b'_'
+        }
+      }));
+      if is_reserved(&w[start..]) {
+        w.splice(start..start, *b"USER_");
+        w.truncate(start + 64);
+      }
+      binary(w, limit, |w| write!(w, "{v}"))?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Default for JournaldSink {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Sink for JournaldSink {
+  fn log(&self, log: &Log, _: &[u8]) -> io::Result<()> {
+    with_scratch(|entry| {
+      self.entry(log, entry, usize::MAX)?;
+
+      let mut socket = self.socket.lock();
+      let socket = match socket.as_mut() {
+        Some(socket) => socket,
+        None => socket.insert(UnixDatagram::unbound()?),
+      };
+      match socket.send_to(entry, &self.path) {
+        // Probably too large for a datagram, so share out a size that is
+        // below the usual limit among the values
+        Err(_) if entry.len() > MAX_TRUNCATED => {
+          entry.clear();
+          let limit = MAX_TRUNCATED / (log.kv.len() + 4);
+          self.entry(log, entry, limit)?;
+          socket.send_to(entry, &self.path).map(|_| ())
+        }
+        result => result.map(|_| ()),
+      }
+    })
+  }
+}
+
+/// The size entries are truncated to when they are too large to send. Linux
+/// allows datagrams up to the socket's send buffer size, which is usually
+/// around 200 KiB.
+const MAX_TRUNCATED: usize = 64 * 1024;
+
+/// Whether `name` is one of the fields every entry has, or one journald
+/// gives a meaning to that users should not set by accident.
+fn is_reserved(name: &[u8]) -> bool {
+  matches!(name, b"MESSAGE" | b"PRIORITY" | b"ERRNO")
+    || name.starts_with(b"SYSLOG_")
+    || name.starts_with(b"CODE_")
+}
+
+/// Writes a field named `name`, with the value `f` writes.
+fn field(
+  w: &mut Vec<u8>,
+  name: &str,
+  limit: usize,
+  f: impl FnOnce(&mut Vec<u8>) -> io::Result<()>,
+) -> io::Result<()> {
+  w.extend_from_slice(name.as_bytes());
+  binary(w, limit, f)
+}
+
+/// Writes the value of a field whose name has already been written.
+///
+/// Values are written in the binary form, prefixed with their length, so they
+/// can contain newlines. Values longer than `limit` bytes are cut short at a
+/// character boundary, and end in `…`.
+fn binary(
+  w: &mut Vec<u8>,
+  limit: usize,
+  f: impl FnOnce(&mut Vec<u8>) -> io::Result<()>,
+) -> io::Result<()> {
+  w.push(b'\n');
+  let len = w.len();
+  w.extend_from_slice(&[0; 8]);
+  f(w)?;
+
+  let start = len + 8;
+  if w.len() - start > limit {
+    let mut end = start + limit.saturating_sub('…'.len_utf8());
+    while end > start && w[end] & 0xc0 == 0x80 {
+      end -= 1;
+    }
+    w.truncate(end);
+    w.extend_from_slice("…".as_bytes());
+  }
+
+  let size = (w.len() - len - 8) as u64;
+  w[len..len + 8].copy_from_slice(&size.to_le_bytes());
+  w.push(b'\n');
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{error, Logger};
+
+  /// Parses an entry into its fields.
+  fn parse(mut entry: &[u8]) -> Vec<(String, String)> {
+    let mut fields = vec![];
+    while !entry.is_empty() {
+      let end = entry.iter().position(|b| *b == b'\n').unwrap();
+      let line = std::str::from_utf8(&entry[..end]).unwrap();
+      entry = &entry[end + 1..];
+
+      match line.split_once('=') {
+        Some((name, value)) => fields.push((name.into(), value.into())),
+        None => {
+          let size = u64::from_le_bytes(entry[..8].try_into().unwrap());
+          let value = &entry[8..8 + size as usize];
+          fields
+            .push((line.into(), String::from_utf8(value.to_vec()).unwrap()));
+          entry = &entry[8 + size as usize + 1..];
+        }
+      }
+    }
+    fields
+  }
+
+  #[test]
+  fn native_protocol() {
+    let path = std::env::temp_dir()
+      .join(format!("lumbermill-journald-{}.sock", std::process::id()));
+    _ = std::fs::remove_file(&path);
+    let journal = UnixDatagram::bind(&path).unwrap();
+
+    let logger = Logger::new()
+      .stdout(false)
+      .sink(JournaldSink::with_path(&path).identifier("tests"));
+    error!(
+      logger: &logger,
+      request.id = 42,
+      _private = "x",
+      priority = "high",
+      code.line = 7,
+      trace = "Line 1\nLine 2",
+      "Request failed"
+    );
+
+    let mut buf = [0; 4096];
+    let len = journal.recv(&mut buf).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let entry = &buf[..len];
+    let trace =
+      [&b"\nTRACE\n"[..], &13u64.to_le_bytes(), b"Line 1\nLine 2\n"].concat();
+    assert!(entry.ends_with(&trace));
+
+    let fields = parse(entry);
+    let fields: Vec<_> = fields
+      .iter()
+      .map(|(k, v)| (k.as_str(), v.as_str()))
+      .collect();
+    assert!(fields[5].1.parse::<u32>().is_ok());
+    assert_eq!(
+      fields,
+      [
+        ("MESSAGE", "Request failed"),
+        ("PRIORITY", "3"),
+        ("SYSLOG_IDENTIFIER", "tests"),
+        ("CODE_MODULE", module_path!()),
+        ("CODE_FILE", file!()),
+        ("CODE_LINE", fields[5].1),
+        ("REQUEST_ID", "42"),
+        ("PRIVATE", "x"),
+        ("USER_PRIORITY", "high"),
+        ("USER_CODE_LINE", "7"),
+        ("TRACE", "Line 1\nLine 2"),
+      ]
+    );
+  }
+
+  #[test]
+  fn truncates_large_entries() {
+    let path = std::env::temp_dir().join(format!(
+      "lumbermill-journald-large-{}.sock",
+      std::process::id()
+    ));
+    _ = std::fs::remove_file(&path);
+    let journal = UnixDatagram::bind(&path).unwrap();
+
+    let logger = Logger::new()
+      .stdout(false)
+      .sink(JournaldSink::with_path(&path).identifier("tests"));
+    let dump = "é".repeat(512 * 1024);
+    error!(logger: &logger, syslog.pid = 1, errno = 2, dump, "Out of memory");
+
+    let mut buf = vec![0; MAX_TRUNCATED];
+    let len = journal.recv(&mut buf).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let fields = parse(&buf[..len]);
+    assert_eq!(fields[0], ("MESSAGE".into(), "Out of memory".into()));
+    assert_eq!(fields[6], ("USER_SYSLOG_PID".into(), "1".into()));
+    assert_eq!(fields[7], ("USER_ERRNO".into(), "2".into()));
+    assert_eq!(fields[8].0, "DUMP");
+    assert!(fields[8].1.ends_with("éé…"));
+  }
+}
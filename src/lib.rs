@@ -19,17 +19,63 @@
 //! Macro documentation delves deeper into what you can supply to them. Refer to
 //! it for details.
 //!
-pub use time::OffsetDateTime;
+//! Key-value pairs that belong to many log lines can be bound to a
+//! [`ChildLogger`] with the [`child!`] macro instead of being repeated.
+//!
+//! With the `tokio` feature enabled, key-value pairs can also be attached to
+//! every log line emitted by a future, see [`WithLogContext`].
+//!
+pub use time::{OffsetDateTime, UtcOffset};
 
+mod child;
+#[cfg(feature = "tokio")]
+mod context;
+mod dispatch;
+mod fields;
 mod file;
+mod fluent;
+pub mod format;
+mod gelf;
+pub mod http;
+#[cfg(unix)]
+mod journald;
 mod log;
 mod logger;
 mod macros;
+mod msgpack;
+mod network;
+mod non_blocking;
+mod protobuf;
+mod record;
+mod sink;
 mod stdout;
+mod syslog;
+pub mod testing;
+mod timestamp;
 
-pub use file::RollInterval;
+pub use child::ChildLogger;
+#[cfg(feature = "tokio")]
+pub use context::WithLogContext;
+pub use dispatch::{scope, with_current_logger, with_logger, Scope};
+pub use fields::Fields;
+pub use file::{FileLogger, RollInterval};
+pub use fluent::FluentSink;
+pub use format::Formatter;
+pub use gelf::GelfSink;
+pub use http::HttpSink;
+#[cfg(unix)]
+pub use journald::JournaldSink;
 pub use log::{Log, LogFormat, LogLevel};
 pub use logger::{Logger, LOGGER};
+pub use network::NetworkSink;
+pub use non_blocking::{
+  Backpressure, NonBlocking, NonBlockingBuilder, WorkerGuard,
+};
+pub use record::Record;
+pub use sink::Sink;
+pub use stdout::{StderrLogger, StdoutLogger};
+pub use syslog::SyslogSink;
+pub use timestamp::{Precision, TimestampFormat};
 
 #[cfg(test)]
 #[ctor::ctor]
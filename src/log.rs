@@ -3,10 +3,18 @@ use std::{
   io,
 };
 
-use owo_colors::{OwoColorize, XtermColors};
+use owo_colors::XtermColors;
 use time::OffsetDateTime;
 
-#[derive(PartialEq, PartialOrd)]
+use crate::{
+  format::{
+    Bunyan, Compact, Ecs, Formatter, Gelf, Json, Logfmt, Pretty,
+    PrettyStructured, Stackdriver, Syslog, Template,
+  },
+  timestamp::TimestampFormat,
+};
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
 pub enum LogLevel {
   Trace,
   Debug,
@@ -29,6 +37,20 @@ impl LogLevel {
   }
 }
 
+impl LogLevel {
+  /// The escape sequence for [`LogLevel::fg_color`]
+  pub(crate) fn ansi_fg(&self) -> &'static str {
+    match self {
+      Self::Trace => "\x1b[38;5;81m",
+      Self::Debug => "\x1b[38;5;202m",
+      Self::Info => "\x1b[38;5;2m",
+      Self::Warn => "\x1b[38;5;3m",
+      Self::Error => "\x1b[38;5;1m",
+      Self::Fatal => "\x1b[38;5;1m",
+    }
+  }
+}
+
 impl Debug for LogLevel {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
@@ -55,12 +77,34 @@ impl Display for LogLevel {
   }
 }
 
+/// The format of log lines. See the [`format`](crate::format) module for what
+/// each one looks like.
 #[derive(Debug)]
 pub enum LogFormat {
   Pretty,
   PrettyStructured,
   Compact,
+  Logfmt,
   Json,
+  /// JSON following the Elastic Common Schema, see [`Ecs`].
+  Ecs,
+  /// JSON for Google Cloud's logging agents, see [`Stackdriver`].
+  Stackdriver(Stackdriver),
+  /// JSON for bunyan and pino tooling, see [`Bunyan`]. Timestamps are always
+  /// in milliseconds or RFC 3339.
+  Bunyan(Bunyan),
+  /// Graylog's GELF, see [`Gelf`]. Timestamps are always in seconds.
+  Gelf(Gelf),
+  /// [RFC 5424](https://datatracker.ietf.org/doc/html/rfc5424) syslog
+  /// messages. Timestamps are always RFC 3339, with microsecond precision.
+  Syslog5424(Syslog),
+  /// Legacy [RFC 3164](https://datatracker.ietf.org/doc/html/rfc3164) syslog
+  /// messages, for receivers that do not understand RFC 5424.
+  Syslog3164(Syslog),
+  /// A format described by a template string, see [`Template`].
+  Template(Template),
+  /// A format of your own, see [`Formatter`].
+  Custom(Box<dyn Formatter>),
 }
 
 pub struct Log<'a> {
@@ -72,168 +116,95 @@ pub struct Log<'a> {
   pub line: u32,
 }
 
+impl LogFormat {
+  /// Whether lines in this format start with a syslog PRI of their own.
+  pub(crate) fn writes_priority(&self) -> bool {
+    matches!(self, Self::Syslog5424(_) | Self::Syslog3164(_))
+  }
+}
+
 impl<'a> Log<'a> {
+  /// Write this log line in `format`, with the default [`TimestampFormat`].
   pub fn write<Writer: io::Write>(
     &self,
     w: &mut Writer,
     format: &LogFormat,
   ) -> io::Result<()> {
-    match format {
-      LogFormat::Pretty => self.pretty(w),
-      LogFormat::PrettyStructured => self.pretty_structured(w),
-      LogFormat::Compact => self.compact(w),
-      LogFormat::Json => self.json(w),
-    }
+    self.write_with_timestamp(w, format, &TimestampFormat::default())
   }
-}
-
-impl<'a> Log<'a> {
-  fn pretty<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-    // Because of the way our macros are set up, the KV list is ordered, which means
-    // that the message will always be the last element
-    let (message, kv) =
-      self.kv.split_last().expect("A log message is required");
 
-    let timestamp = format!(
-      "{:0>2}:{:0>2}:{:0>2}.{:0>3}Z",
-      self.timestamp.hour(),
-      self.timestamp.minute(),
-      self.timestamp.second(),
-      self.timestamp.millisecond()
-    );
-    write!(w, "{} ", timestamp.dimmed())?;
-
-    write!(w, "{} ", self.level.color(self.level.fg_color()))?;
-
-    if self.level == LogLevel::Error || self.level == LogLevel::Fatal {
-      write!(w, "{} ", message.1.red())?;
-    } else {
-      write!(w, "{} ", message.1)?;
-    }
-
-    kv.iter().try_for_each(|(k, v)| {
-      write!(w, "{}{}{} ", k.dimmed(), "=".dimmed(), v)
-    })?;
-
-    write!(w, "{}{} ", "mod=".dimmed(), self.module)?;
-    write!(w, "{}{}:{} ", "src=".dimmed(), self.file, self.line)?;
-
-    writeln!(w)?;
-
-    Ok(())
-  }
-
-  fn pretty_structured<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-    // Because of the way our macros are set up, the KV list is ordered, which means
-    // that the message will always be the last element
-    let (message, kv) =
-      self.kv.split_last().expect("A log message is required");
-
-    let timestamp = format!(
-      "{}T{:0>2}:{:0>2}:{:0>2}.{:0>3}Z",
-      self.timestamp.date(),
-      self.timestamp.hour(),
-      self.timestamp.minute(),
-      self.timestamp.second(),
-      self.timestamp.millisecond()
-    );
-    write!(w, "{}{} ", "ts=".dimmed(), timestamp.dimmed())?;
-
-    write!(
-      w,
-      "{}{:?} ",
-      "level=".dimmed(),
-      self.level.color(self.level.fg_color())
-    )?;
-
-    if self.level == LogLevel::Error || self.level == LogLevel::Fatal {
-      write!(w, "{}\"{}\" ", "message=".dimmed(), message.1.red())?;
-    } else {
-      write!(w, "{}\"{}\" ", "message=".dimmed(), message.1)?;
+  /// Write this log line in `format`. Custom formats ignore `timestamp`.
+  pub fn write_with_timestamp<Writer: io::Write>(
+    &self,
+    w: &mut Writer,
+    format: &LogFormat,
+    timestamp: &TimestampFormat,
+  ) -> io::Result<()> {
+    let timestamp = *timestamp;
+    match format {
+      LogFormat::Pretty => Pretty { timestamp }.format(self, w),
+      LogFormat::PrettyStructured => {
+        PrettyStructured { timestamp }.format(self, w)
+      }
+      LogFormat::Compact => Compact { timestamp }.format(self, w),
+      LogFormat::Logfmt => Logfmt { timestamp }.format(self, w),
+      LogFormat::Json => Json { timestamp }.format(self, w),
+      LogFormat::Ecs => Ecs { timestamp }.format(self, w),
+      LogFormat::Stackdriver(stackdriver) => Stackdriver {
+        timestamp,
+        ..*stackdriver
+      }
+      .format(self, w),
+      LogFormat::Bunyan(bunyan) => bunyan.format(self, w),
+      LogFormat::Gelf(gelf) => gelf.format(self, w),
+      LogFormat::Syslog5424(syslog) => syslog.write_5424(self, w),
+      LogFormat::Syslog3164(syslog) => syslog.write_3164(self, w),
+      LogFormat::Template(template) => template.write(self, w, &timestamp),
+      LogFormat::Custom(formatter) => formatter.format(self, w),
     }
-
-    kv.iter().try_for_each(|(k, v)| {
-      write!(w, "{}{}{} ", k.dimmed(), "=".dimmed(), v.cyan())
-    })?;
-
-    let module = format!("mod={}", self.module);
-    write!(w, "{} ", module.dimmed())?;
-    let source = format!("src={}:{}", self.file, self.line);
-    write!(w, "{} ", source.dimmed())?;
-
-    writeln!(w)?;
-
-    Ok(())
-  }
-
-  fn compact<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-    // Because of the way our macros are set up, the KV list is ordered, which means
-    // that the message will always be the last element
-    let (message, kv) =
-      self.kv.split_last().expect("A log message is required");
-
-    // I'd like to use `time::format_description::well_known::Rfc3339` here but
-    // it formats time with ns precision, I want ms precision.
-    let t = &self.timestamp;
-    write!(
-      w,
-      "ts={}T{:0>2}:{:0>2}:{:0>2}.{:0>3}Z ",
-      t.date(),
-      t.hour(),
-      t.minute(),
-      t.second(),
-      t.millisecond()
-    )?;
-
-    write!(w, "level={:?} ", self.level)?;
-    write!(w, "message=\"{}\" ", message.1)?;
-    kv.iter().try_for_each(|(k, v)| write!(w, "{}={} ", k, v))?;
-    write!(w, "mod={} ", self.module)?;
-    write!(w, "src={}:{}", self.file, self.line)?;
-
-    writeln!(w)?;
-
-    Ok(())
-  }
-
-  fn json<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
-    // Because of the way our macros are set up, the KV list is ordered, which means
-    // that the message will always be the last element
-    let (message, kv) =
-      self.kv.split_last().expect("A log message is required");
-
-    write!(w, "{{",)?;
-
-    // I'd like to use `time::format_description::well_known::Rfc3339` here but
-    // it formats time with ns precision, I want ms precision.
-    let t = &self.timestamp;
-    write!(
-      w,
-      "\"ts\":\"{}T{:0>2}:{:0>2}:{:0>2}.{:0>3}Z\",",
-      t.date(),
-      t.hour(),
-      t.minute(),
-      t.second(),
-      t.millisecond()
-    )?;
-
-    write!(w, "\"level\":\"{:?}\",", self.level)?;
-    write!(w, "\"message\":\"{}\",", message.1)?;
-    kv.iter()
-      .try_for_each(|(k, v)| write!(w, "\"{}\":\"{}\",", k, v))?;
-    write!(w, "\"mod\":\"{}\",", self.module)?;
-    write!(w, "\"src\":\"{}:{}\"", self.file, self.line)?;
-
-    write!(w, "}}")?;
-    writeln!(w)?;
-
-    Ok(())
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::Precision;
+
+  #[test]
+  fn pretty() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("key1", format_args!("value1")),
+        ("message", format_args!("logmsg")),
+      ],
+      module: "tests",
+      file: "log.rs",
+      line: 10,
+    };
+    let mut w: Vec<u8> = vec![];
+    log.write(&mut w, &LogFormat::Pretty).unwrap();
+    assert_eq!(String::from_utf8(w).unwrap(), "\x1b[2m00:00:00.000Z\x1b[0m \x1b[38;5;2mINF\x1b[39m logmsg \x1b[2mkey1\x1b[0m\x1b[2m=\x1b[0mvalue1 \x1b[2mmod=\x1b[0mtests \x1b[2msrc=\x1b[0mlog.rs:10 \n");
+  }
+
+  #[test]
+  fn pretty_structured() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Error,
+      kv: &[
+        ("key1", format_args!("value1")),
+        ("message", format_args!("logmsg")),
+      ],
+      module: "tests",
+      file: "log.rs",
+      line: 10,
+    };
+    let mut w: Vec<u8> = vec![];
+    log.write(&mut w, &LogFormat::PrettyStructured).unwrap();
+    assert_eq!(String::from_utf8(w).unwrap(), "\x1b[2mts=\x1b[0m\x1b[2m1970-01-01T00:00:00.000Z\x1b[0m \x1b[2mlevel=\x1b[0m\x1b[38;5;1merror\x1b[39m \x1b[2mmessage=\x1b[0m\"\x1b[31mlogmsg\x1b[39m\" \x1b[2mkey1\x1b[0m\x1b[2m=\x1b[0m\x1b[36mvalue1\x1b[39m \x1b[2mmod=tests\x1b[0m \x1b[2msrc=log.rs:10\x1b[0m \n");
+  }
 
   #[test]
   fn compact() {
@@ -273,4 +244,91 @@ mod tests {
     log.write(&mut w, &LogFormat::Json).unwrap();
     assert_eq!(String::from_utf8(w).unwrap(), "{\"ts\":\"1970-01-01T00:00:00.000Z\",\"level\":\"info\",\"message\":\"logmsg\",\"key1\":\"value1\",\"key1.2\":\"value1.2\",\"mod\":\"tests\",\"src\":\"log.rs:10\"}\n");
   }
+
+  #[test]
+  fn json_collisions() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("level", format_args!("custom")),
+        ("src", format_args!("db")),
+        ("message", format_args!("logmsg")),
+      ],
+      module: "tests",
+      file: "log.rs",
+      line: 10,
+    };
+    let mut w: Vec<u8> = vec![];
+
+    log.write(&mut w, &LogFormat::Json).unwrap();
+    assert_eq!(String::from_utf8(w).unwrap(), "{\"ts\":\"1970-01-01T00:00:00.000Z\",\"level\":\"info\",\"message\":\"logmsg\",\"_level\":\"custom\",\"_src\":\"db\",\"mod\":\"tests\",\"src\":\"log.rs:10\"}\n");
+  }
+
+  #[test]
+  fn control_characters() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("key1", format_args!("value1\x1b[2J")),
+        ("message", format_args!("logmsg\nts=0 level=fatal")),
+      ],
+      module: "tests",
+      file: "log.rs",
+      line: 10,
+    };
+
+    for format in [LogFormat::Pretty, LogFormat::Compact] {
+      let mut w: Vec<u8> = vec![];
+      log.write(&mut w, &format).unwrap();
+      let line = String::from_utf8(w).unwrap();
+
+      assert_eq!(line.lines().count(), 1);
+      assert!(line.contains("logmsg\\nts=0 level=fatal"));
+      assert!(line.contains("value1\\x1b[2J"));
+    }
+
+    let mut w: Vec<u8> = vec![];
+    log.write(&mut w, &LogFormat::Json).unwrap();
+    let line = String::from_utf8(w).unwrap();
+    assert!(line.contains("\"logmsg\\nts=0 level=fatal\""));
+    assert!(line.contains("\"value1\\u001b[2J\""));
+  }
+
+  #[test]
+  fn timestamps() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[("message", format_args!("logmsg"))],
+      module: "tests",
+      file: "log.rs",
+      line: 10,
+    };
+    let write = |format, timestamp| {
+      let mut w: Vec<u8> = vec![];
+      log
+        .write_with_timestamp(&mut w, &format, &timestamp)
+        .unwrap();
+      String::from_utf8(w).unwrap()
+    };
+
+    assert_eq!(
+      write(LogFormat::Compact, TimestampFormat::None),
+      "level=info message=\"logmsg\" mod=tests src=log.rs:10\n"
+    );
+    assert_eq!(
+      write(LogFormat::Json, TimestampFormat::Unix(Precision::Millis)),
+      "{\"ts\":0,\"level\":\"info\",\"message\":\"logmsg\",\"mod\":\"tests\",\"src\":\"log.rs:10\"}\n"
+    );
+
+    // Custom timestamps are escaped in JSON strings
+    let custom = TimestampFormat::Custom(time::macros::format_description!(
+      "\"[year]\"\\"
+    ));
+    for format in [LogFormat::Json, LogFormat::Ecs] {
+      assert!(write(format, custom).contains(r#"":"\"1970\"\\","#));
+    }
+  }
 }
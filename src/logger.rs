@@ -1,20 +1,56 @@
-use std::{path::PathBuf, sync::OnceLock};
+use std::{cell::Cell, io, path::PathBuf, sync::OnceLock};
+
+use time::UtcOffset;
 
 use crate::{
+  child::ChildLogger,
+  fields::Fields,
   file::FileLogger,
+  format::{Bunyan, Formatter, Gelf, Stackdriver, Template},
   log::{Log, LogFormat, LogLevel},
-  stdout::StdoutLogger,
+  sink::Sink,
+  stdout::{write_prefix, StderrLogger, StdoutLogger},
+  timestamp::TimestampFormat,
   RollInterval,
 };
 
+/// The default logger. The logging macros use it unless they are given a
+/// `logger:`, or are called from inside [`with_logger`](crate::with_logger).
+/// Set it with [`Logger::init`].
 pub static LOGGER: OnceLock<Logger> = OnceLock::new();
 
+/// Buffers larger than this are not kept around after a line is written, so a
+/// single huge line does not pin memory for the lifetime of a thread.
+const MAX_BUFFER_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+  static BUFFER: Cell<Vec<u8>> = const { Cell::new(Vec::new()) };
+}
+
+/// A logging pipeline: a minimum level, a format and the places logs go to.
+///
+/// A `Logger` does not need to be global. Any number of them can live in the
+/// same process, and the logging macros can target one directly:
+///
+/// ```
+/// use lumbermill::{info, Logger};
+///
+/// let audit = Logger::default().json();
+/// info!(logger: &audit, user = "toph", "Signed in");
+/// ```
+///
+/// Calling [`Logger::init`] makes it the default target of the macros instead.
 #[derive(Debug)]
 pub struct Logger {
   level: LogLevel,
   format: LogFormat,
+  timestamp: TimestampFormat,
+  offset: UtcOffset,
   stdout: Option<StdoutLogger>,
+  stderr: Option<StderrLogger>,
+  level_prefix: Option<bool>,
   file: Option<FileLogger>,
+  sinks: Vec<Box<dyn Sink>>,
 }
 
 impl Logger {
@@ -38,6 +74,24 @@ impl Logger {
     self
   }
 
+  /// Format log lines with a [`Formatter`] of your own. Shorthand for
+  /// `.format(LogFormat::Custom(Box::new(formatter)))`.
+  pub fn formatter<F: Formatter + 'static>(self, formatter: F) -> Self {
+    self.format(LogFormat::Custom(Box::new(formatter)))
+  }
+
+  /// Format log lines with a [`Template`].
+  ///
+  /// # Panics
+  /// If `template` is invalid. Use [`Template::new`] and
+  /// [`LogFormat::Template`] to handle errors instead.
+  pub fn template(self, template: &str) -> Self {
+    match Template::new(template) {
+      Ok(template) => self.format(LogFormat::Template(template)),
+      Err(e) => panic!("Invalid log template: {e}"),
+    }
+  }
+
   pub fn pretty(self) -> Self {
     self.format(LogFormat::Pretty)
   }
@@ -50,17 +104,73 @@ impl Logger {
     self.format(LogFormat::Compact)
   }
 
+  pub fn logfmt(self) -> Self {
+    self.format(LogFormat::Logfmt)
+  }
+
   pub fn json(self) -> Self {
     self.format(LogFormat::Json)
   }
 
+  pub fn ecs(self) -> Self {
+    self.format(LogFormat::Ecs)
+  }
+
+  pub fn stackdriver(self) -> Self {
+    self.format(LogFormat::Stackdriver(Stackdriver::default()))
+  }
+
+  /// Log in a format bunyan and pino tooling understands, as the application
+  /// `name`.
+  pub fn bunyan(self, name: &str) -> Self {
+    self.format(LogFormat::Bunyan(Bunyan::new(name)))
+  }
+
+  /// Log in Graylog's GELF, with the hostname of this machine as the `host`.
+  pub fn gelf(self) -> Self {
+    self.format(LogFormat::Gelf(Gelf::new()))
+  }
+
+  /// How timestamps are rendered. Does not apply to custom formats.
+  pub fn timestamp(mut self, timestamp: TimestampFormat) -> Self {
+    self.timestamp = timestamp;
+    self
+  }
+
+  /// Render timestamps at `offset` instead of in UTC.
+  ///
+  /// The offset is fixed, so it does not follow daylight saving changes while
+  /// the program runs. There is no built-in local time option because looking
+  /// up the local offset needs `time`'s `local-offset` feature. To log in local
+  /// time, enable it and pass `UtcOffset::current_local_offset()`. It is best
+  /// determined early in `main`, because `time` refuses to on some platforms
+  /// once other threads are running.
+  pub fn utc_offset(mut self, offset: UtcOffset) -> Self {
+    self.offset = offset;
+    self
+  }
+
   pub fn stdout(mut self, s: bool) -> Self {
-    if s {
-      self.stdout = Some(StdoutLogger::new())
-    } else {
-      self.stdout = None;
-    }
+    self.stdout = s.then(StdoutLogger::new);
+    self
+  }
 
+  /// Log to `stderr`, in addition to `stdout` unless that is turned off with
+  /// `.stdout(false)`.
+  pub fn stderr(mut self, s: bool) -> Self {
+    self.stderr = s.then(StderrLogger::new);
+    self
+  }
+
+  /// Prefix lines on `stdout` and `stderr` with their syslog priority (like
+  /// `<6>`), so systemd can tell levels apart (see `sd-daemon(3)`). Otherwise,
+  /// every line would be logged with the same priority.
+  ///
+  /// Enabled by default for streams connected to the journal. Syslog formats
+  /// already start with a priority, and are never prefixed. Files and other
+  /// sinks are not affected.
+  pub fn level_prefix(mut self, level_prefix: bool) -> Self {
+    self.level_prefix = Some(level_prefix);
     self
   }
 
@@ -73,26 +183,130 @@ impl Logger {
     self
   }
 
+  /// Send logs to `sink`, in addition to `stdout` and log files. Can be called
+  /// multiple times to add multiple sinks.
+  ///
+  /// Sinks write on the thread that logs. Wrap ones that talk to the network,
+  /// and especially ones that batch like [`HttpSink`](crate::HttpSink), in a
+  /// [`NonBlocking`](crate::NonBlocking) sink, which writes and flushes them
+  /// in the background.
+  ///
+  /// # Examples
+  /// ```no_run
+  /// use lumbermill::{http::Loki, HttpSink, Logger, NonBlocking};
+  ///
+  /// let loki = HttpSink::new("http://127.0.0.1:3100/loki/api/v1/push", Loki::new())
+  ///   .unwrap();
+  /// let (loki, _guard) = NonBlocking::new(loki);
+  /// Logger::default().sink(loki).init();
+  /// ```
+  pub fn sink<S: Sink + 'static>(mut self, sink: S) -> Self {
+    self.sinks.push(Box::new(sink));
+    self
+  }
+
+  /// Create a [`ChildLogger`] that logs to this logger with `fields` bound to it.
+  pub fn child(&self, fields: Fields) -> ChildLogger<'_> {
+    ChildLogger::with_logger(self, fields)
+  }
+
+  /// Make this logger the default target of the logging macros.
   pub fn init(self) {
     LOGGER
       .set(self)
       .expect("Loggers can only be initialized once");
   }
 
+  pub fn enabled(&self, level: LogLevel) -> bool {
+    level >= self.level
+  }
+
   pub fn log(&self, log: Log) {
-    if log.level < self.level {
+    if !self.enabled(log.level) {
       return;
     }
 
-    _ = self
-      .stdout
-      .as_ref()
-      .map(|logger| logger.log(&log, &self.format));
+    #[cfg(feature = "tokio")]
+    if let Some(()) = crate::context::with_current(|fields| {
+      crate::fields::with_fields(fields, log.kv, |kv| {
+        self.write(&Log { kv, ..log })
+      })
+    }) {
+      return;
+    }
 
-    _ = self
-      .file
-      .as_ref()
-      .map(|logger| logger.log(&log, &self.format));
+    self.write(&log);
+  }
+
+  /// Write out anything the sinks buffered, see [`Sink::flush`].
+  pub fn flush(&self) {
+    for sink in &self.sinks {
+      _ = sink.flush();
+    }
+  }
+
+  fn write(&self, log: &Log) {
+    // Lines are formatted once, into a buffer that is reused for every line
+    // logged on this thread, and the same bytes are handed to every sink. If a
+    // sink logs while a line is being written, the buffer has already been
+    // taken, and the nested line gets a fresh one.
+    let log = &Log {
+      timestamp: log.timestamp.to_offset(self.offset),
+      ..*log
+    };
+    let mut buffer = BUFFER.try_with(Cell::take).unwrap_or_default();
+    buffer.clear();
+
+    let stdout_prefix = self.prefixes(self.stdout.as_ref().map(|s| s.journal));
+    let stderr_prefix = self.prefixes(self.stderr.as_ref().map(|s| s.journal));
+
+    if let Ok(start) =
+      self.format_line(log, &mut buffer, stdout_prefix || stderr_prefix)
+    {
+      let line = &buffer[start..];
+      let prefixed = |prefix| if prefix { &buffer[..] } else { line };
+
+      _ = self
+        .stdout
+        .as_ref()
+        .map(|s| s.log(log, prefixed(stdout_prefix)));
+      _ = self
+        .stderr
+        .as_ref()
+        .map(|s| s.log(log, prefixed(stderr_prefix)));
+      _ = self.file.as_ref().map(|logger| logger.log(log, line));
+
+      for sink in &self.sinks {
+        _ = sink.log(log, line);
+      }
+    }
+
+    if buffer.capacity() <= MAX_BUFFER_CAPACITY {
+      _ = BUFFER.try_with(|b| b.set(buffer));
+    }
+  }
+
+  /// Whether lines on a console stream get a level prefix. `journal` is whether
+  /// the stream is connected to the journal, or `None` without a stream.
+  fn prefixes(&self, journal: Option<bool>) -> bool {
+    journal.is_some_and(|journal| self.level_prefix.unwrap_or(journal))
+      && !self.format.writes_priority()
+  }
+
+  /// Formats `log` into `buffer`, after its level prefix with `prefix`.
+  /// Returns where the line itself starts.
+  fn format_line(
+    &self,
+    log: &Log,
+    buffer: &mut Vec<u8>,
+    prefix: bool,
+  ) -> io::Result<usize> {
+    if prefix {
+      write_prefix(buffer, log.level)?;
+    }
+    let start = buffer.len();
+    log.write_with_timestamp(buffer, &self.format, &self.timestamp)?;
+    Ok(start)
   }
 }
 
@@ -101,21 +315,60 @@ impl Default for Logger {
     Self {
       level: LogLevel::Info,
       format: LogFormat::Pretty,
+      timestamp: TimestampFormat::default(),
+      offset: UtcOffset::UTC,
       stdout: Some(StdoutLogger::new()),
+      stderr: None,
+      level_prefix: None,
       file: None,
+      sinks: Vec::new(),
     }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::{log::Log, OffsetDateTime, LOGGER};
+  use std::fs;
+
+  use crate::{
+    child, format::Syslog, info, log::Log, testing::CaptureSink, LogFormat,
+    LogLevel, Logger, OffsetDateTime, RollInterval,
+  };
+
+  #[test]
+  fn independent_instances() {
+    let dir = std::env::temp_dir()
+      .join(format!("lumbermill-instances-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let logger = Logger::new()
+      .stdout(false)
+      .compact()
+      .file(&dir, RollInterval::None);
+
+    info!(logger: &logger, count = 9001, "Too many items in queue");
+    let db_log = child!(logger: &logger, component = "db");
+    info!(logger: db_log, "Connected");
+    drop(db_log);
+    drop(logger);
+
+    let logs = fs::read_to_string(dir.join("log.log")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let lines: Vec<_> = logs.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("message=\"Too many items in queue\" count=9001"));
+    assert!(lines[1].contains("message=\"Connected\" component=db"));
+  }
 
   #[test]
   fn stdout() {
-    LOGGER.get().unwrap().log(Log {
+    let capture = CaptureSink::new();
+    let logger = Logger::default().sink(capture.clone());
+
+    logger.log(Log {
       timestamp: OffsetDateTime::now_utc(),
-      level: crate::LogLevel::Trace,
+      level: LogLevel::Trace,
       module: module_path!(),
       file: file!(),
       line: line!(),
@@ -129,9 +382,9 @@ mod tests {
       ],
     });
 
-    LOGGER.get().unwrap().log(Log {
+    logger.log(Log {
       timestamp: OffsetDateTime::now_utc(),
-      level: crate::LogLevel::Debug,
+      level: LogLevel::Debug,
       module: module_path!(),
       file: file!(),
       line: line!(),
@@ -141,9 +394,9 @@ mod tests {
       ],
     });
 
-    LOGGER.get().unwrap().log(Log {
+    logger.log(Log {
       timestamp: OffsetDateTime::now_utc(),
-      level: crate::LogLevel::Info,
+      level: LogLevel::Info,
       module: module_path!(),
       file: file!(),
       line: line!(),
@@ -153,9 +406,9 @@ mod tests {
       ],
     });
 
-    LOGGER.get().unwrap().log(Log {
+    logger.log(Log {
       timestamp: OffsetDateTime::now_utc(),
-      level: crate::LogLevel::Warn,
+      level: LogLevel::Warn,
       module: module_path!(),
       file: file!(),
       line: line!(),
@@ -165,9 +418,9 @@ mod tests {
       ],
     });
 
-    LOGGER.get().unwrap().log(Log {
+    logger.log(Log {
       timestamp: OffsetDateTime::now_utc(),
-      level: crate::LogLevel::Error,
+      level: LogLevel::Error,
       module: module_path!(),
       file: file!(),
       line: line!(),
@@ -177,9 +430,9 @@ mod tests {
       ],
     });
 
-    LOGGER.get().unwrap().log(Log {
+    logger.log(Log {
       timestamp: OffsetDateTime::now_utc(),
-      level: crate::LogLevel::Fatal,
+      level: LogLevel::Fatal,
       module: module_path!(),
       file: file!(),
       line: line!(),
@@ -188,5 +441,47 @@ mod tests {
         ("message", format_args!("Out of memory")),
       ],
     });
+
+    let levels: Vec<_> = capture.logs().iter().map(|log| log.level).collect();
+    assert_eq!(
+      levels,
+      [
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+        LogLevel::Fatal
+      ]
+    );
+  }
+
+  #[test]
+  fn level_prefixes() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Warn,
+      module: module_path!(),
+      file: file!(),
+      line: line!(),
+      kv: &[("message", format_args!("Too many items in queue"))],
+    };
+    let line = |logger: &Logger| {
+      let mut buffer = vec![];
+      let prefix = logger.prefixes(Some(false));
+      let start = logger.format_line(&log, &mut buffer, prefix).unwrap();
+      (String::from_utf8(buffer).unwrap(), start)
+    };
+
+    let logger = Logger::new().stdout(false).json();
+    assert_eq!(line(&logger).1, 0);
+    assert!(line(&logger).0.starts_with("{"));
+
+    let logger = logger.level_prefix(true);
+    assert_eq!(line(&logger).1, 3);
+    assert!(line(&logger).0.starts_with("<4>{"));
+    assert!(!logger.prefixes(None));
+
+    let logger = logger.format(LogFormat::Syslog5424(Syslog::new()));
+    assert_eq!(line(&logger).1, 0);
+    assert!(line(&logger).0.starts_with("<12>1 "));
   }
 }
@@ -36,9 +36,20 @@
 /// trace!(addr.ip = ?ip, addr.port = port, "Listening on {}", port);
 /// // Or in the shorthand notation:
 /// trace!(?addr.ip, port, "Listening on {}", port);
+///
+/// // Log through a different logger than the global one
+/// let logger = Logger::default().compact();
+/// trace!(logger: &logger, "Listening on {}", port);
+///
+/// // Log through a child logger, which attaches its own key-value pairs
+/// let conn_log = lumbermill::child!(addr.ip, addr.port);
+/// trace!(logger: conn_log, "Connection accepted");
 /// ```
 #[macro_export]
 macro_rules! trace {
+  (logger: $logger:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $crate::LogLevel::Trace, $($fields)+)
+  };
   ($($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($crate::LogLevel::Trace, $($k).+ = $v, $($fields)*)
   };
@@ -62,6 +73,9 @@ macro_rules! trace {
 /// and examples.
 #[macro_export]
 macro_rules! debug {
+  (logger: $logger:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $crate::LogLevel::Debug, $($fields)+)
+  };
   ($($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($crate::LogLevel::Debug, $($k).+ = $v, $($fields)*)
   };
@@ -85,6 +99,9 @@ macro_rules! debug {
 /// and examples.
 #[macro_export]
 macro_rules! info {
+  (logger: $logger:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $crate::LogLevel::Info, $($fields)+)
+  };
   ($($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($crate::LogLevel::Info, $($k).+ = $v, $($fields)*)
   };
@@ -108,6 +125,9 @@ macro_rules! info {
 /// and examples.
 #[macro_export]
 macro_rules! warn {
+  (logger: $logger:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $crate::LogLevel::Warn, $($fields)+)
+  };
   ($($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($crate::LogLevel::Warn, $($k).+ = $v, $($fields)*)
   };
@@ -131,6 +151,9 @@ macro_rules! warn {
 /// and examples.
 #[macro_export]
 macro_rules! error {
+  (logger: $logger:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $crate::LogLevel::Error, $($fields)+)
+  };
   ($($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($crate::LogLevel::Error, $($k).+ = $v, $($fields)*)
   };
@@ -154,6 +177,9 @@ macro_rules! error {
 /// and examples.
 #[macro_export]
 macro_rules! fatal {
+  (logger: $logger:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $crate::LogLevel::Fatal, $($fields)+)
+  };
   ($($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($crate::LogLevel::Fatal, $($k).+ = $v, $($fields)*)
   };
@@ -180,6 +206,9 @@ macro_rules! fatal {
 /// must always be a log level. Refer to it for details and examples.
 #[macro_export]
 macro_rules! log {
+  (logger: $logger:expr, $lvl:expr, $($fields:tt)+) => {
+    $crate::__internal_log!(logger: $logger, $lvl, $($fields)+)
+  };
   ($lvl:expr, $($k:ident).+ = $v:expr, $($fields:tt)*) => {
     $crate::__internal_log!($lvl, $($k).+ = $v, $($fields)*)
   };
@@ -197,6 +226,140 @@ macro_rules! log {
   };
 }
 
+/// Build an owned list of key-value pairs.
+///
+/// Accepts the same key-value syntax as [`trace!`], without the trailing format
+/// string. Values are formatted eagerly, so the resulting [`Fields`](crate::Fields)
+/// can be kept around and attached to log lines later.
+///
+/// # Examples
+/// ```
+/// use lumbermill::fields;
+///
+/// let request_id = 42;
+/// let path = "/health";
+///
+/// let fields = fields!(request_id, ?path, http.method = "GET");
+/// assert_eq!(fields.len(), 3);
+/// ```
+#[macro_export]
+macro_rules! fields {
+  ($($fields:tt)*) => {
+    $crate::Fields::from($crate::__internal_fields!({ }, $($fields)*,))
+  };
+}
+
+/// Create a [`ChildLogger`](crate::ChildLogger) with key-value pairs bound to it.
+///
+/// Accepts the same key-value syntax as [`fields!`]. Pass the result to any of
+/// the logging macros with `logger:` to log through it.
+///
+/// The child logs wherever the logging macros do (see
+/// [`with_current_logger`](crate::with_current_logger)), unless a parent is
+/// supplied with `logger:`. The parent can be a [`Logger`](crate::Logger) or
+/// another child.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{child, info, Logger};
+///
+/// let db_log = child!(component = "db", shard = 3);
+/// info!(logger: db_log, "Connected");
+///
+/// let logger = Logger::default();
+/// let http_log = child!(logger: &logger, component = "http");
+/// info!(logger: http_log, "Listening");
+/// ```
+#[macro_export]
+macro_rules! child {
+  (logger: $logger:expr, $($fields:tt)*) => {
+    $logger.child($crate::fields!($($fields)*))
+  };
+  ($($fields:tt)*) => {
+    $crate::ChildLogger::new($crate::fields!($($fields)*))
+  };
+}
+
+/// Internal-only, do not use directly. Munches key-value pairs for [`fields!`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __internal_fields {
+  ({ $($kv:expr),* }, $($k:ident).+ = $v:expr, $($fields:tt)*) => {
+    $crate::__internal_fields!({ $($kv,)* (stringify!($($k).+), format!("{}", $v)) }, $($fields)*)
+  };
+  ({ $($kv:expr),* }, $($k:ident).+ = ?$v:expr, $($fields:tt)*) => {
+    $crate::__internal_fields!({ $($kv,)* (stringify!($($k).+), format!("{:?}", $v)) }, $($fields)*)
+  };
+  ({ $($kv:expr),* }, $($k:ident).+, $($fields:tt)*) => {
+    $crate::__internal_fields!({ $($kv,)* (stringify!($($k).+), format!("{}", $($k).+)) }, $($fields)*)
+  };
+  ({ $($kv:expr),* }, ?$($k:ident).+, $($fields:tt)*) => {
+    $crate::__internal_fields!({ $($kv,)* (stringify!($($k).+), format!("{:?}", $($k).+)) }, $($fields)*)
+  };
+  ({ $($kv:expr),* }, $(,)?) => {
+    vec![ $($kv,)* ]
+  };
+}
+
+/// Assert that a matching log line was captured.
+///
+/// The first argument is the level the log line must have been logged at. It
+/// can be followed by any number of matchers on its key-value pairs (the message
+/// is available under the `message` key):
+///
+/// - `key = value` matches lines where `key` was logged with exactly `value`
+/// - `key contains value` matches lines where `key`'s value contains `value`
+///
+/// Lines are checked against the innermost [`testing::capture`](crate::testing::capture),
+/// or against a [`CaptureSink`](crate::testing::CaptureSink) supplied with
+/// `sink:`. The assertion passes if at least one line matches.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{assert_logged, testing, warn, LogLevel};
+///
+/// testing::capture(|| {
+///   warn!(count = 9001, "Too many items in queue");
+///
+///   assert_logged!(LogLevel::Warn, message contains "queue", count = "9001");
+/// });
+///
+/// let sink = testing::CaptureSink::new();
+/// let logger = lumbermill::Logger::new().stdout(false).sink(sink.clone());
+/// warn!(logger: &logger, "Disk almost full");
+/// assert_logged!(sink: sink, LogLevel::Warn, message = "Disk almost full");
+/// ```
+#[macro_export]
+macro_rules! assert_logged {
+  (sink: $sink:expr, $lvl:expr) => {
+    $crate::testing::assert_logged(&$sink, $lvl, &[])
+  };
+  (sink: $sink:expr, $lvl:expr, $($matchers:tt)*) => {
+    $crate::testing::assert_logged(&$sink, $lvl, &$crate::__internal_matchers!({ }, $($matchers)*,))
+  };
+  ($lvl:expr) => {
+    $crate::testing::assert_logged(&$crate::testing::current(), $lvl, &[])
+  };
+  ($lvl:expr, $($matchers:tt)*) => {
+    $crate::testing::assert_logged(&$crate::testing::current(), $lvl, &$crate::__internal_matchers!({ }, $($matchers)*,))
+  };
+}
+
+/// Internal-only, do not use directly. Munches matchers for [`assert_logged!`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __internal_matchers {
+  ({ $($m:expr),* }, $($k:ident).+ contains $v:expr, $($matchers:tt)*) => {
+    $crate::__internal_matchers!({ $($m,)* $crate::testing::Matcher::Contains(stringify!($($k).+), format!("{}", $v)) }, $($matchers)*)
+  };
+  ({ $($m:expr),* }, $($k:ident).+ = $v:expr, $($matchers:tt)*) => {
+    $crate::__internal_matchers!({ $($m,)* $crate::testing::Matcher::Equals(stringify!($($k).+), format!("{}", $v)) }, $($matchers)*)
+  };
+  ({ $($m:expr),* }, $(,)?) => {
+    [ $($m,)* ]
+  };
+}
+
 /// Internal-only, do not use directly. All public macros converge here.
 #[doc(hidden)]
 #[macro_export]
@@ -221,14 +384,22 @@ macro_rules! __internal_log {
     &[ $($kv,)* ]
   };
 
-  // entrypoint
+  // entrypoints
+  (logger: $logger:expr, $lvl:expr, $($fields:tt)+) => {
+    $logger.log($crate::Log {
+      timestamp: $crate::OffsetDateTime::now_utc(),
+      level: $lvl,
+      module: module_path!(),
+      file: file!(),
+      line: line!(),
+      kv: $crate::__internal_log!({ }, $($fields)*)
+    })
+  };
   ($lvl:expr, $($fields:tt)+) => {
     // Ignore returned Option so the lumbermill can be used in other libraries.
     // It also allows lumbermill to be completely optimized away if a Logger is
     // never initialized.
-    _ = $crate::LOGGER
-      .get()
-      .map(|l|
+    _ = $crate::with_current_logger(|l|
         l.log($crate::Log {
           timestamp: $crate::OffsetDateTime::now_utc(),
           level: $lvl,
@@ -339,6 +510,23 @@ mod tests {
     trace!(ip = ?addr.ip, port = ?addr.port, "Message");
   }
 
+  #[test]
+  fn logger() {
+    let ip = "0.0.0.0";
+    let port = 7096;
+    let conn_log = child!(ip, port);
+
+    log!(logger: conn_log, LogLevel::Info, "Message");
+    log!(logger: &conn_log, LogLevel::Info, ?ip, "Message {}", ip);
+    trace!(logger: conn_log, "Message");
+    trace!(logger: conn_log, ip, ?port, "Message {ip}:{port}");
+    debug!(logger: conn_log, "Message");
+    info!(logger: conn_log, "Message");
+    warn!(logger: conn_log, "Message");
+    error!(logger: conn_log, "Message");
+    fatal!(logger: conn_log, "Message");
+  }
+
   #[test]
   fn mixed() {
     let ip = "0.0.0.0";
@@ -0,0 +1,176 @@
+//! Just enough of [MessagePack](https://msgpack.org) to send logs to
+//! collectors that speak it.
+
+use std::io::{self, Read, Write};
+
+pub(crate) fn write_array_len<W: Write + ?Sized>(
+  w: &mut W,
+  len: usize,
+) -> io::Result<()> {
+  match len {
+    0..=15 => w.write_all(&[0x90 | len as u8]),
+    16..=0xffff => {
+      w.write_all(&[0xdc])?;
+      w.write_all(&(len as u16).to_be_bytes())
+    }
+    _ => {
+      w.write_all(&[0xdd])?;
+      w.write_all(&(len as u32).to_be_bytes())
+    }
+  }
+}
+
+pub(crate) fn write_map_len<W: Write + ?Sized>(
+  w: &mut W,
+  len: usize,
+) -> io::Result<()> {
+  match len {
+    0..=15 => w.write_all(&[0x80 | len as u8]),
+    16..=0xffff => {
+      w.write_all(&[0xde])?;
+      w.write_all(&(len as u16).to_be_bytes())
+    }
+    _ => {
+      w.write_all(&[0xdf])?;
+      w.write_all(&(len as u32).to_be_bytes())
+    }
+  }
+}
+
+pub(crate) fn write_str<W: Write + ?Sized>(
+  w: &mut W,
+  s: &str,
+) -> io::Result<()> {
+  let len = s.len();
+  match len {
+    0..=31 => w.write_all(&[0xa0 | len as u8])?,
+    32..=0xff => w.write_all(&[0xd9, len as u8])?,
+    0x100..=0xffff => {
+      w.write_all(&[0xda])?;
+      w.write_all(&(len as u16).to_be_bytes())?
+    }
+    _ => {
+      w.write_all(&[0xdb])?;
+      w.write_all(&(len as u32).to_be_bytes())?
+    }
+  }
+  w.write_all(s.as_bytes())
+}
+
+pub(crate) fn write_uint<W: Write + ?Sized>(
+  w: &mut W,
+  n: u64,
+) -> io::Result<()> {
+  match n {
+    0..=0x7f => w.write_all(&[n as u8]),
+    0x80..=0xff => w.write_all(&[0xcc, n as u8]),
+    0x100..=0xffff => {
+      w.write_all(&[0xcd])?;
+      w.write_all(&(n as u16).to_be_bytes())
+    }
+    0x1_0000..=0xffff_ffff => {
+      w.write_all(&[0xce])?;
+      w.write_all(&(n as u32).to_be_bytes())
+    }
+    _ => {
+      w.write_all(&[0xcf])?;
+      w.write_all(&n.to_be_bytes())
+    }
+  }
+}
+
+/// Writes an 8 byte extension value of type `kind`.
+pub(crate) fn write_fixext8<W: Write + ?Sized>(
+  w: &mut W,
+  kind: i8,
+  data: [u8; 8],
+) -> io::Result<()> {
+  w.write_all(&[0xd7, kind as u8])?;
+  w.write_all(&data)
+}
+
+/// Reads a map of strings to strings, like the acknowledgements collectors
+/// send back. Values of other types are skipped over if they are simple, and
+/// are an error otherwise.
+pub(crate) fn read_str_map<R: Read>(
+  r: &mut R,
+) -> io::Result<Vec<(String, Option<String>)>> {
+  let len = match read_u8(r)? {
+    b @ 0x80..=0x8f => (b & 0x0f) as usize,
+    0xde => read_u16(r)? as usize,
+    _ => return Err(invalid("Expected a map")),
+  };
+
+  (0..len)
+    .map(|_| {
+      let key = read_str(r)?.ok_or_else(|| invalid("Expected a string key"))?;
+      Ok((key, read_str(r)?))
+    })
+    .collect()
+}
+
+/// Reads a string, or `None` for `nil`, booleans and small integers.
+fn read_str<R: Read>(r: &mut R) -> io::Result<Option<String>> {
+  let len = match read_u8(r)? {
+    b @ 0xa0..=0xbf => (b & 0x1f) as usize,
+    0xd9 => read_u8(r)? as usize,
+    0xda => read_u16(r)? as usize,
+    0xc0 | 0xc2 | 0xc3 | 0x00..=0x7f => return Ok(None),
+    _ => return Err(invalid("Expected a string")),
+  };
+
+  let mut buf = vec![0; len];
+  r.read_exact(&mut buf)?;
+  String::from_utf8(buf)
+    .map(Some)
+    .map_err(|_| invalid("Invalid UTF-8"))
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+  let mut buf = [0; 1];
+  r.read_exact(&mut buf)?;
+  Ok(buf[0])
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+  let mut buf = [0; 2];
+  r.read_exact(&mut buf)?;
+  Ok(u16::from_be_bytes(buf))
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn encoding() {
+    let mut w = vec![];
+    write_array_len(&mut w, 3).unwrap();
+    write_str(&mut w, "a").unwrap();
+    write_uint(&mut w, 300).unwrap();
+    write_map_len(&mut w, 20).unwrap();
+    write_str(&mut w, &"x".repeat(40)).unwrap();
+    assert_eq!(w[..8], [0x93, 0xa1, b'a', 0xcd, 0x01, 0x2c, 0xde, 0x00]);
+    assert_eq!(w[8..11], [20, 0xd9, 40]);
+  }
+
+  #[test]
+  fn decoding() {
+    let mut w = vec![];
+    write_map_len(&mut w, 2).unwrap();
+    write_str(&mut w, "ack").unwrap();
+    write_str(&mut w, "abc").unwrap();
+    write_str(&mut w, "ok").unwrap();
+    w.push(0xc3);
+
+    assert_eq!(
+      read_str_map(&mut &w[..]).unwrap(),
+      [("ack".into(), Some("abc".into())), ("ok".into(), None)]
+    );
+    assert!(read_str_map(&mut &[0x91][..]).is_err());
+  }
+}
@@ -0,0 +1,497 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::{
+  collections::VecDeque,
+  fs::{File, OpenOptions},
+  io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+  net::{TcpStream, UdpSocket},
+  path::PathBuf,
+  sync::atomic::{AtomicU64, Ordering},
+  time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+  log::Log,
+  sink::{connect_any, udp_socket, Sink},
+};
+
+#[derive(Debug)]
+enum Transport {
+  Tcp(String),
+  Udp(String),
+  #[cfg(unix)]
+  Unix(PathBuf),
+}
+
+#[derive(Debug)]
+enum Connection {
+  Tcp(TcpStream),
+  Udp(UdpSocket),
+  #[cfg(unix)]
+  Unix(UnixStream),
+}
+
+#[derive(Debug)]
+struct Spill {
+  path: PathBuf,
+  max_bytes: u64,
+  file: Option<File>,
+  /// Bytes in the file, which can be left over from a previous run
+  len: u64,
+  /// Bytes at the start of the file that have already been sent
+  sent: u64,
+}
+
+#[derive(Debug)]
+struct State {
+  connection: Option<Connection>,
+  /// When to try connecting again, after connecting failed
+  retry_at: Option<Instant>,
+  backoff: Duration,
+  buffer: VecDeque<Vec<u8>>,
+  buffered: usize,
+  spill: Option<Spill>,
+}
+
+/// A [`Sink`] that writes formatted log lines to a collector (like Vector or
+/// Fluent Bit) over TCP, UDP or a Unix socket.
+///
+/// Lines are written as they are formatted by the [`Logger`](crate::Logger),
+/// one per line (or one per datagram over UDP), so pair it with a format the
+/// collector can parse, like [`LogFormat::Json`](crate::LogFormat::Json).
+///
+/// The connection is established when the first line is logged. While it is
+/// down, lines are kept in an in-memory buffer (1 MiB by default), and
+/// reconnecting is retried with exponential backoff. Once the buffer is full,
+/// lines go to a spill file on disk, if one is configured with
+/// [`NetworkSink::spill`], and are dropped otherwise. Buffered lines are sent,
+/// oldest first, as soon as the connection is back up, or when the sink is
+/// flushed or dropped. Lines that were being written when the connection broke
+/// may be sent twice.
+///
+/// Connecting and writing happens on the thread that logs, wrap the sink in a
+/// [`NonBlocking`](crate::NonBlocking) sink to keep a slow collector from
+/// stalling it.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{Logger, NetworkSink};
+///
+/// let vector = NetworkSink::tcp("127.0.0.1:9000")
+///   .buffer_capacity(8 * 1024 * 1024)
+///   .spill("/var/spool/app/logs", 512 * 1024 * 1024);
+/// Logger::default().json().sink(vector).init();
+/// ```
+#[derive(Debug)]
+pub struct NetworkSink {
+  transport: Transport,
+  capacity: usize,
+  initial_backoff: Duration,
+  max_backoff: Duration,
+  timeout: Duration,
+  state: Mutex<State>,
+  dropped: AtomicU64,
+}
+
+impl NetworkSink {
+  /// Connect to `addr` (like `"127.0.0.1:9000"`) over TCP. The address is
+  /// resolved again every time the sink reconnects.
+  pub fn tcp(addr: &str) -> Self {
+    Self::new(Transport::Tcp(addr.to_owned()))
+  }
+
+  /// Send datagrams to `addr` (like `"127.0.0.1:9000"`) over UDP.
+  pub fn udp(addr: &str) -> Self {
+    Self::new(Transport::Udp(addr.to_owned()))
+  }
+
+  /// Connect to the Unix stream socket at `path`.
+  #[cfg(unix)]
+  pub fn unix<P: Into<PathBuf>>(path: P) -> Self {
+    Self::new(Transport::Unix(path.into()))
+  }
+
+  fn new(transport: Transport) -> Self {
+    let initial_backoff = Duration::from_millis(100);
+    Self {
+      transport,
+      capacity: 1024 * 1024,
+      initial_backoff,
+      max_backoff: Duration::from_secs(30),
+      timeout: Duration::from_secs(5),
+      state: Mutex::new(State {
+        connection: None,
+        retry_at: None,
+        backoff: initial_backoff,
+        buffer: VecDeque::new(),
+        buffered: 0,
+        spill: None,
+      }),
+      dropped: AtomicU64::new(0),
+    }
+  }
+
+  /// How many bytes of log lines to keep in memory while disconnected.
+  pub fn buffer_capacity(mut self, bytes: usize) -> Self {
+    self.capacity = bytes;
+    self
+  }
+
+  /// Keep up to `max_bytes` of log lines in the file at `path` once the
+  /// in-memory buffer is full. Lines left in the file by a previous run are
+  /// sent once the sink connects.
+  pub fn spill<P: Into<PathBuf>>(self, path: P, max_bytes: u64) -> Self {
+    let path = path.into();
+    let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    self.state.lock().spill = Some(Spill {
+      path,
+      max_bytes,
+      file: None,
+      len,
+      sent: 0,
+    });
+    self
+  }
+
+  /// How long to wait before reconnecting the first time, and at most. The
+  /// wait doubles with every failed attempt. Defaults to 100ms and 30s.
+  pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+    self.initial_backoff = initial;
+    self.max_backoff = max;
+    self.state.get_mut().backoff = initial;
+    self
+  }
+
+  /// How long connecting and writing may take. Defaults to 5 seconds.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Number of log lines dropped because both buffers were full.
+  pub fn dropped(&self) -> u64 {
+    self.dropped.load(Ordering::Relaxed)
+  }
+
+  fn connect(&self) -> io::Result<Connection> {
+    match &self.transport {
+      Transport::Tcp(addr) => {
+        let stream = connect_any(addr, |addr| {
+          TcpStream::connect_timeout(&addr, self.timeout)
+        })?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        Ok(Connection::Tcp(stream))
+      }
+      Transport::Udp(addr) => {
+        Ok(Connection::Udp(connect_any(addr, udp_socket)?))
+      }
+      #[cfg(unix)]
+      Transport::Unix(path) => {
+        let stream = UnixStream::connect(path)?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        Ok(Connection::Unix(stream))
+      }
+    }
+  }
+
+  /// Makes sure there is a connection with nothing buffered ahead of new
+  /// lines, connecting and flushing buffers if needed. The connection is
+  /// dropped if flushing fails.
+  fn ready(&self, state: &mut State) -> io::Result<()> {
+    if state.connection.is_some() {
+      return state.flush().map_err(|e| {
+        state.connection = None;
+        e
+      });
+    }
+
+    if state.retry_at.is_some_and(|at| Instant::now() < at) {
+      return Err(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "Waiting to reconnect",
+      ));
+    }
+
+    // A connection that breaks while catching up counts as a failed attempt,
+    // so a collector that keeps dropping it is not hammered
+    let result = self.connect().and_then(|connection| {
+      state.connection = Some(connection);
+      state.flush()
+    });
+    match result {
+      Ok(()) => {
+        state.retry_at = None;
+        state.backoff = self.initial_backoff;
+        Ok(())
+      }
+      Err(e) => {
+        state.connection = None;
+        state.retry_at = Some(Instant::now() + state.backoff);
+        state.backoff = (state.backoff * 2).min(self.max_backoff);
+        Err(e)
+      }
+    }
+  }
+}
+
+impl Connection {
+  fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+    match self {
+      Self::Tcp(stream) => stream.write_all(bytes),
+      Self::Udp(socket) => socket.send(bytes).map(|_| ()),
+      #[cfg(unix)]
+      Self::Unix(stream) => stream.write_all(bytes),
+    }
+  }
+}
+
+impl State {
+  /// Sends buffered lines, oldest first.
+  fn flush(&mut self) -> io::Result<()> {
+    let connection = match self.connection.as_mut() {
+      Some(connection) => connection,
+      None => return Ok(()),
+    };
+
+    while let Some(line) = self.buffer.front() {
+      connection.send(line)?;
+      self.buffered -= line.len();
+      self.buffer.pop_front();
+    }
+
+    if let Some(spill) = self.spill.as_mut().filter(|spill| spill.len > 0) {
+      // The file is read a line at a time, since it can be much larger than
+      // the in-memory buffer. Datagrams carry a single line.
+      let mut file = File::open(&spill.path)?;
+      file.seek(SeekFrom::Start(spill.sent))?;
+      let mut lines = BufReader::with_capacity(64 * 1024, file);
+      let mut line = Vec::new();
+      while lines.read_until(b'\n', &mut line)? > 0 {
+        connection.send(&line)?;
+        spill.sent += line.len() as u64;
+        line.clear();
+      }
+
+      spill.truncate()?;
+    }
+
+    Ok(())
+  }
+
+  /// Keeps `line` until it can be sent. Returns `false` if there is no room.
+  fn keep(&mut self, line: &[u8], capacity: usize) -> bool {
+    let spilling = self.spill.as_ref().is_some_and(|spill| spill.len > 0);
+    if !spilling && self.buffered + line.len() <= capacity {
+      self.buffered += line.len();
+      self.buffer.push_back(line.to_vec());
+      return true;
+    }
+
+    match self.spill.as_mut() {
+      Some(spill) => spill.append(line).is_ok(),
+      None => false,
+    }
+  }
+}
+
+impl Spill {
+  fn append(&mut self, line: &[u8]) -> io::Result<()> {
+    if self.len + line.len() as u64 > self.max_bytes {
+      return Err(io::Error::new(io::ErrorKind::Other, "Spill file is full"));
+    }
+
+    let file = match self.file.as_mut() {
+      Some(file) => file,
+      None => self.file.insert(
+        OpenOptions::new()
+          .create(true)
+          .append(true)
+          .open(&self.path)?,
+      ),
+    };
+    file.write_all(line)?;
+    self.len += line.len() as u64;
+
+    Ok(())
+  }
+
+  fn truncate(&mut self) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).open(&self.path)?;
+    file.set_len(0)?;
+    if let Some(file) = self.file.as_mut() {
+      file.seek(SeekFrom::Start(0))?;
+    }
+    self.len = 0;
+    self.sent = 0;
+
+    Ok(())
+  }
+}
+
+impl Sink for NetworkSink {
+  fn log(&self, _: &Log, formatted: &[u8]) -> io::Result<()> {
+    let mut state = self.state.lock();
+
+    let result = self.ready(&mut state).and_then(|_| {
+      // Safe to unwrap, `ready` connected
+      state.connection.as_mut().unwrap().send(formatted)
+    });
+
+    if let Err(e) = result {
+      if !matches!(e.kind(), io::ErrorKind::NotConnected) {
+        state.connection = None;
+      }
+      if !state.keep(formatted, self.capacity) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+      }
+      return Err(e);
+    }
+
+    Ok(())
+  }
+
+  /// Sends buffered lines, if the sink is (or can get) connected.
+  fn flush(&self) -> io::Result<()> {
+    self.ready(&mut self.state.lock())
+  }
+}
+
+impl Drop for NetworkSink {
+  fn drop(&mut self) {
+    _ = self.flush();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Read, net::TcpListener};
+
+  use super::*;
+  use crate::{info, Logger};
+
+  fn lines<R: Read>(reader: R, count: usize) -> Vec<String> {
+    BufReader::new(reader)
+      .lines()
+      .take(count)
+      .map(|line| line.unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn tcp() {
+    let collector = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = collector.local_addr().unwrap().to_string();
+    let logger = Logger::new()
+      .stdout(false)
+      .template("{message}")
+      .sink(NetworkSink::tcp(&addr));
+
+    info!(logger: &logger, "First");
+    info!(logger: &logger, "Second");
+
+    let (stream, _) = collector.accept().unwrap();
+    assert_eq!(lines(stream, 2), ["First", "Second"]);
+  }
+
+  #[test]
+  fn udp() {
+    for local in ["127.0.0.1:0", "[::1]:0"] {
+      // Not every machine has IPv6
+      let Ok(collector) = UdpSocket::bind(local) else {
+        continue;
+      };
+      collector
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+      let addr = collector.local_addr().unwrap().to_string();
+      let logger = Logger::new()
+        .stdout(false)
+        .template("{message}")
+        .sink(NetworkSink::udp(&addr));
+
+      info!(logger: &logger, "Datagram");
+
+      let mut buf = [0; 64];
+      let len = collector.recv(&mut buf).unwrap();
+      assert_eq!(&buf[..len], b"Datagram\n");
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn buffers_while_disconnected() {
+    use std::os::unix::net::UnixListener;
+
+    let dir = std::env::temp_dir()
+      .join(format!("lumbermill-network-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket = dir.join("collector.sock");
+    let spill = dir.join("spill.log");
+    _ = std::fs::remove_file(&socket);
+
+    let sink = NetworkSink::unix(&socket)
+      .buffer_capacity(14)
+      .spill(&spill, 14)
+      .backoff(Duration::ZERO, Duration::ZERO);
+    let logger = Logger::new().stdout(false).template("{message}").sink(sink);
+
+    // "Line 1" and "Line 2" fit in memory, "Line 3" and "Line 4" are spilled,
+    // and there is no room for "Line 5"
+    for i in 1..=5 {
+      info!(logger: &logger, "Line {i}");
+    }
+    assert_eq!(std::fs::read_to_string(&spill).unwrap(), "Line 3\nLine 4\n");
+
+    let collector = UnixListener::bind(&socket).unwrap();
+    logger.flush();
+    assert_eq!(std::fs::read_to_string(&spill).unwrap(), "");
+    info!(logger: &logger, "Line 6");
+    let (stream, _) = collector.accept().unwrap();
+    assert_eq!(
+      lines(stream, 5),
+      ["Line 1", "Line 2", "Line 3", "Line 4", "Line 6"]
+    );
+
+    // Lines still buffered are sent when the sink is dropped
+    drop(collector);
+    std::fs::remove_file(&socket).unwrap();
+    info!(logger: &logger, "Line 7");
+    let collector = UnixListener::bind(&socket).unwrap();
+    drop(logger);
+    let (stream, _) = collector.accept().unwrap();
+    assert_eq!(lines(stream, 1), ["Line 7"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn backs_off() {
+    // Nothing listens on the port once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0")
+      .and_then(|listener| listener.local_addr())
+      .unwrap()
+      .to_string();
+    let sink = NetworkSink::tcp(&addr)
+      .backoff(Duration::from_secs(60), Duration::from_secs(60));
+    let log = |sink: &NetworkSink| {
+      sink.log(
+        &Log {
+          timestamp: time::OffsetDateTime::UNIX_EPOCH,
+          level: crate::LogLevel::Info,
+          kv: &[("message", format_args!("logmsg"))],
+          module: "tests",
+          file: "network.rs",
+          line: 10,
+        },
+        b"logmsg\n",
+      )
+    };
+
+    assert_ne!(log(&sink).unwrap_err().kind(), io::ErrorKind::NotConnected);
+    // The next attempt is a minute away
+    assert_eq!(log(&sink).unwrap_err().kind(), io::ErrorKind::NotConnected);
+    assert_eq!(sink.state.lock().buffer.len(), 2);
+  }
+}
@@ -0,0 +1,434 @@
+use std::{
+  collections::VecDeque,
+  io,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::{log::Log, sink::Sink, Record};
+
+/// What a [`NonBlocking`] sink does with a log line when its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+  /// Wait for the worker to make room. No log lines are lost, but logging can
+  /// stall if the wrapped sink cannot keep up.
+  Block,
+  /// Drop the log line that did not fit.
+  DropNewest,
+  /// Drop the oldest log line in the queue to make room.
+  DropOldest,
+}
+
+#[derive(Debug)]
+struct Queue {
+  records: VecDeque<(Record, Vec<u8>)>,
+  shutdown: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+  queue: Mutex<Queue>,
+  not_empty: Condvar,
+  not_full: Condvar,
+  capacity: usize,
+  backpressure: Backpressure,
+  dropped: AtomicU64,
+}
+
+/// A [`Sink`] that hands log lines off to a worker thread, which writes them to
+/// another sink.
+///
+/// Logging through it only costs a copy of the log line into a bounded queue,
+/// so slow sinks (a busy disk, for example) do not stall the threads that log.
+/// When the queue is full, log lines are handled according to its
+/// [`Backpressure`] policy.
+///
+/// The worker thread runs until the [`WorkerGuard`] returned alongside the sink
+/// is dropped, at which point it writes out whatever is left in the queue. Keep
+/// the guard alive for as long as you log, usually by binding it in `main`.
+///
+/// # Examples
+/// ```
+/// use lumbermill::{Backpressure, FileLogger, Logger, NonBlocking, RollInterval};
+///
+/// # let dir = std::env::temp_dir().join("lumbermill-non-blocking-doctest");
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// let (file, _guard) = NonBlocking::builder()
+///   .capacity(16_384)
+///   .backpressure(Backpressure::DropOldest)
+///   .build(FileLogger::new(dir, RollInterval::Daily));
+///
+/// Logger::default().stdout(false).sink(file).init();
+/// ```
+#[derive(Debug, Clone)]
+pub struct NonBlocking {
+  shared: Arc<Shared>,
+}
+
+impl NonBlocking {
+  /// Wrap `sink` with the default configuration. See [`NonBlockingBuilder`].
+  pub fn new<S: Sink + 'static>(sink: S) -> (NonBlocking, WorkerGuard) {
+    Self::builder().build(sink)
+  }
+
+  pub fn builder() -> NonBlockingBuilder {
+    NonBlockingBuilder::default()
+  }
+
+  /// Number of log lines dropped because the queue was full (or the worker had
+  /// already shut down).
+  pub fn dropped(&self) -> u64 {
+    self.shared.dropped.load(Ordering::Relaxed)
+  }
+}
+
+impl Sink for NonBlocking {
+  fn log(&self, log: &Log, formatted: &[u8]) -> io::Result<()> {
+    let record = log.to_owned();
+    let formatted = formatted.to_vec();
+    let shared = &self.shared;
+    let mut queue = shared.queue.lock();
+
+    loop {
+      if queue.shutdown {
+        shared.dropped.fetch_add(1, Ordering::Relaxed);
+        return Ok(());
+      }
+
+      if queue.records.len() < shared.capacity {
+        break;
+      }
+
+      match shared.backpressure {
+        Backpressure::Block => shared.not_full.wait(&mut queue),
+        Backpressure::DropNewest => {
+          shared.dropped.fetch_add(1, Ordering::Relaxed);
+          return Ok(());
+        }
+        Backpressure::DropOldest => {
+          queue.records.pop_front();
+          shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+      }
+    }
+
+    queue.records.push_back((record, formatted));
+    drop(queue);
+    shared.not_empty.notify_one();
+
+    Ok(())
+  }
+}
+
+/// Configures a [`NonBlocking`] sink.
+///
+/// By default, the queue holds 8192 log lines and drops new ones when full.
+#[derive(Debug)]
+pub struct NonBlockingBuilder {
+  capacity: usize,
+  backpressure: Backpressure,
+  flush_interval: Duration,
+}
+
+impl NonBlockingBuilder {
+  pub fn capacity(mut self, capacity: usize) -> Self {
+    self.capacity = capacity.max(1);
+    self
+  }
+
+  pub fn backpressure(mut self, backpressure: Backpressure) -> Self {
+    self.backpressure = backpressure;
+    self
+  }
+
+  /// Flush the wrapped sink once the lines written to it have waited this
+  /// long, whether more lines keep coming in or not, and when shutting down.
+  /// Sinks that batch by time rely on this to send out their last lines.
+  /// Defaults to 1 second, and is at least 10ms.
+  pub fn flush_interval(mut self, interval: Duration) -> Self {
+    self.flush_interval = interval.max(Duration::from_millis(10));
+    self
+  }
+
+  pub fn build<S: Sink + 'static>(self, sink: S) -> (NonBlocking, WorkerGuard) {
+    let shared = Arc::new(Shared {
+      queue: Mutex::new(Queue {
+        records: VecDeque::with_capacity(self.capacity),
+        shutdown: false,
+      }),
+      not_empty: Condvar::new(),
+      not_full: Condvar::new(),
+      capacity: self.capacity,
+      backpressure: self.backpressure,
+      dropped: AtomicU64::new(0),
+    });
+
+    let worker = thread::Builder::new()
+      .name(String::from("lumbermill-worker"))
+      .spawn({
+        let shared = Arc::clone(&shared);
+        let flush_interval = self.flush_interval;
+        move || work(&shared, sink, flush_interval)
+      })
+      .expect("Must be able to spawn the lumbermill worker thread");
+
+    (
+      NonBlocking {
+        shared: Arc::clone(&shared),
+      },
+      WorkerGuard {
+        shared,
+        worker: Some(worker),
+      },
+    )
+  }
+}
+
+impl Default for NonBlockingBuilder {
+  fn default() -> Self {
+    Self {
+      capacity: 8192,
+      backpressure: Backpressure::DropNewest,
+      flush_interval: Duration::from_secs(1),
+    }
+  }
+}
+
+fn work<S: Sink>(shared: &Shared, sink: S, flush_interval: Duration) {
+  let mut batch = Vec::new();
+  // When the oldest line written since the last flush was written
+  let mut unflushed: Option<Instant> = None;
+
+  loop {
+    {
+      let mut queue = shared.queue.lock();
+      while queue.records.is_empty() && !queue.shutdown {
+        match unflushed {
+          Some(since) => {
+            let wait = flush_interval.saturating_sub(since.elapsed());
+            if shared.not_empty.wait_for(&mut queue, wait).timed_out() {
+              break;
+            }
+          }
+          None => shared.not_empty.wait(&mut queue),
+        }
+      }
+
+      if queue.records.is_empty() {
+        // Either idle for long enough to flush, or shut down
+        let shutdown = queue.shutdown;
+        drop(queue);
+        if unflushed.take().is_some() {
+          _ = sink.flush();
+        }
+        if shutdown {
+          return;
+        }
+        continue;
+      }
+
+      batch.extend(queue.records.drain(..));
+    }
+    shared.not_full.notify_all();
+
+    for (record, formatted) in batch.drain(..) {
+      _ = record.with_log(|log| sink.log(log, &formatted));
+    }
+    let since = *unflushed.get_or_insert_with(Instant::now);
+    if since.elapsed() >= flush_interval {
+      _ = sink.flush();
+      unflushed = None;
+    }
+  }
+}
+
+/// Keeps the worker thread of a [`NonBlocking`] sink running. When dropped,
+/// the worker writes out all queued log lines and exits.
+#[derive(Debug)]
+#[must_use = "Dropping the guard immediately shuts down the worker thread"]
+pub struct WorkerGuard {
+  shared: Arc<Shared>,
+  worker: Option<JoinHandle<()>>,
+}
+
+impl WorkerGuard {
+  /// Number of log lines dropped because the queue was full (or the worker had
+  /// already shut down).
+  pub fn dropped(&self) -> u64 {
+    self.shared.dropped.load(Ordering::Relaxed)
+  }
+}
+
+impl Drop for WorkerGuard {
+  fn drop(&mut self) {
+    self.shared.queue.lock().shutdown = true;
+    self.shared.not_empty.notify_all();
+    self.shared.not_full.notify_all();
+
+    if let Some(worker) = self.worker.take() {
+      _ = worker.join();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::mpsc::{channel, Sender};
+
+  use super::*;
+  use crate::{info, testing::CaptureSink, Logger};
+
+  /// Signals when the worker starts writing, then waits for `gate` to open.
+  struct Gated {
+    started: Mutex<Sender<()>>,
+    gate: Arc<Mutex<()>>,
+    inner: CaptureSink,
+  }
+
+  impl Sink for Gated {
+    fn log(&self, log: &Log, formatted: &[u8]) -> io::Result<()> {
+      _ = self.started.lock().send(());
+      drop(self.gate.lock());
+      self.inner.log(log, formatted)
+    }
+  }
+
+  fn stalled(backpressure: Backpressure) -> (Vec<String>, u64) {
+    let capture = CaptureSink::new();
+    let gate = Arc::new(Mutex::new(()));
+    let (started, worker_started) = channel();
+
+    let (sink, guard) = NonBlocking::builder()
+      .capacity(2)
+      .backpressure(backpressure)
+      .build(Gated {
+        started: Mutex::new(started),
+        gate: Arc::clone(&gate),
+        inner: capture.clone(),
+      });
+    let logger = Logger::new().stdout(false).sink(sink);
+
+    let closed = gate.lock();
+    info!(logger: &logger, "0");
+    worker_started.recv().unwrap();
+    for i in 1..=4 {
+      info!(logger: &logger, "{i}");
+    }
+    drop(closed);
+
+    let dropped = guard.dropped();
+    drop(guard);
+
+    let messages = capture
+      .logs()
+      .iter()
+      .map(|log| log.message().to_owned())
+      .collect();
+    (messages, dropped)
+  }
+
+  #[test]
+  fn drains_on_drop() {
+    let capture = CaptureSink::new();
+    let (sink, guard) = NonBlocking::new(capture.clone());
+    let logger = Logger::new().stdout(false).sink(sink);
+
+    for i in 0..1000 {
+      info!(logger: &logger, i, "Message");
+    }
+    drop(guard);
+
+    assert_eq!(capture.logs().len(), 1000);
+    assert_eq!(capture.logs()[999].get("i"), Some("999"));
+  }
+
+  #[test]
+  fn drop_newest() {
+    let (messages, dropped) = stalled(Backpressure::DropNewest);
+    assert_eq!(messages, ["0", "1", "2"]);
+    assert_eq!(dropped, 2);
+  }
+
+  #[test]
+  fn drop_oldest() {
+    let (messages, dropped) = stalled(Backpressure::DropOldest);
+    assert_eq!(messages, ["0", "3", "4"]);
+    assert_eq!(dropped, 2);
+  }
+
+  #[test]
+  fn block() {
+    let capture = CaptureSink::new();
+    let (sink, guard) = NonBlocking::builder()
+      .capacity(1)
+      .backpressure(Backpressure::Block)
+      .build(capture.clone());
+    let logger = Logger::new().stdout(false).sink(sink);
+
+    for i in 0..100 {
+      info!(logger: &logger, i, "Message");
+    }
+    assert_eq!(guard.dropped(), 0);
+    drop(guard);
+
+    assert_eq!(capture.logs().len(), 100);
+  }
+
+  #[test]
+  fn flushes_periodically() {
+    struct Flushes(Mutex<Sender<()>>);
+
+    impl Sink for Flushes {
+      fn log(&self, _: &Log, _: &[u8]) -> io::Result<()> {
+        Ok(())
+      }
+
+      fn flush(&self) -> io::Result<()> {
+        _ = self.0.lock().send(());
+        Ok(())
+      }
+    }
+
+    // Lines are flushed once, when shutting down
+    let (flushed, flushes) = channel();
+    let (sink, guard) = NonBlocking::builder()
+      .flush_interval(Duration::from_secs(3600))
+      .build(Flushes(Mutex::new(flushed)));
+    let logger = Logger::new().stdout(false).sink(sink);
+    for i in 0..100 {
+      info!(logger: &logger, i, "Message");
+    }
+    drop(guard);
+    assert_eq!(flushes.try_iter().count(), 1);
+
+    // Lines are flushed once they waited for the interval, and nothing is
+    // flushed after that
+    let (flushed, flushes) = channel();
+    let (sink, guard) = NonBlocking::builder()
+      .flush_interval(Duration::from_millis(10))
+      .build(Flushes(Mutex::new(flushed)));
+    let logger = Logger::new().stdout(false).sink(sink);
+    info!(logger: &logger, "Message");
+    flushes.recv_timeout(Duration::from_secs(5)).unwrap();
+    drop(guard);
+    assert_eq!(flushes.try_iter().count(), 0);
+  }
+
+  #[test]
+  fn after_shutdown() {
+    let capture = CaptureSink::new();
+    let (sink, guard) = NonBlocking::new(capture.clone());
+    let logger = Logger::new().stdout(false).sink(sink.clone());
+    drop(guard);
+
+    info!(logger: &logger, "Too late");
+    assert_eq!(sink.dropped(), 1);
+    assert!(capture.logs().is_empty());
+  }
+}
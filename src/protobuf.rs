@@ -0,0 +1,78 @@
+//! Just enough of [Protocol Buffers](https://protobuf.dev/programming-guides/encoding/)
+//! to export logs to collectors that speak it.
+
+const VARINT: u32 = 0;
+const FIXED64: u32 = 1;
+const LEN: u32 = 2;
+
+fn write_varint(w: &mut Vec<u8>, mut n: u64) {
+  while n >= 0x80 {
+    w.push(n as u8 | 0x80);
+    n >>= 7;
+  }
+  w.push(n as u8);
+}
+
+fn write_tag(w: &mut Vec<u8>, field: u32, wire_type: u32) {
+  write_varint(w, u64::from(field << 3 | wire_type));
+}
+
+pub(crate) fn write_uint(w: &mut Vec<u8>, field: u32, n: u64) {
+  write_tag(w, field, VARINT);
+  write_varint(w, n);
+}
+
+/// Writes an `int64`. Negative numbers take up 10 bytes, like in protobuf.
+pub(crate) fn write_int(w: &mut Vec<u8>, field: u32, n: i64) {
+  write_uint(w, field, n as u64);
+}
+
+pub(crate) fn write_fixed64(w: &mut Vec<u8>, field: u32, n: u64) {
+  write_tag(w, field, FIXED64);
+  w.extend_from_slice(&n.to_le_bytes());
+}
+
+pub(crate) fn write_bytes(w: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+  write_tag(w, field, LEN);
+  write_varint(w, bytes.len() as u64);
+  w.extend_from_slice(bytes);
+}
+
+pub(crate) fn write_string(w: &mut Vec<u8>, field: u32, s: &str) {
+  write_bytes(w, field, s.as_bytes());
+}
+
+/// Writes the message `f` writes as a field. Messages are prefixed with their
+/// length, so they are written to a separate buffer first.
+pub(crate) fn write_message(
+  w: &mut Vec<u8>,
+  field: u32,
+  f: impl FnOnce(&mut Vec<u8>),
+) {
+  let mut message = vec![];
+  f(&mut message);
+  write_bytes(w, field, &message);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn encoding() {
+    let mut w = vec![];
+    write_uint(&mut w, 1, 150);
+    write_string(&mut w, 2, "testing");
+    write_message(&mut w, 3, |w| write_int(w, 1, -1));
+    write_fixed64(&mut w, 4, 1);
+
+    assert_eq!(w[..3], [0x08, 0x96, 0x01]);
+    assert_eq!(w[3..12], *b"\x12\x07testing");
+    assert_eq!(w[12..14], [0x1a, 11]);
+    assert_eq!(
+      w[14..25],
+      [0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+    );
+    assert_eq!(w[25..], [0x21, 1, 0, 0, 0, 0, 0, 0, 0]);
+  }
+}
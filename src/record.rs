@@ -0,0 +1,123 @@
+use std::io;
+
+use time::OffsetDateTime;
+
+use crate::{
+  fields::with_fields,
+  log::{Log, LogFormat, LogLevel},
+  Fields,
+};
+
+/// An owned version of a [`Log`].
+///
+/// A `Log` borrows its key-value pairs from the call site, so it cannot outlive
+/// the logging macro that created it. A `Record` owns everything, with values
+/// rendered to strings, so it can be stored, queued or sent to another thread.
+/// Create one with [`Log::to_owned`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+  pub timestamp: OffsetDateTime,
+  pub level: LogLevel,
+  /// Key-value pairs, including the message (always the last pair).
+  pub kv: Fields,
+  pub module: String,
+  pub file: String,
+  pub line: u32,
+}
+
+impl Record {
+  pub fn message(&self) -> &str {
+    self.get("message").unwrap_or_default()
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.kv.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+  }
+
+  /// Calls `f` with a [`Log`] borrowing from this record.
+  pub fn with_log<R>(&self, f: impl FnOnce(&Log) -> R) -> R {
+    with_fields(&self.kv, &[], |kv| {
+      f(&Log {
+        timestamp: self.timestamp,
+        level: self.level,
+        kv,
+        module: &self.module,
+        file: &self.file,
+        line: self.line,
+      })
+    })
+  }
+
+  pub fn write<Writer: io::Write>(
+    &self,
+    w: &mut Writer,
+    format: &LogFormat,
+  ) -> io::Result<()> {
+    self.with_log(|log| log.write(w, format))
+  }
+}
+
+impl<'a> Log<'a> {
+  pub fn to_owned(&self) -> Record {
+    Record {
+      timestamp: self.timestamp,
+      level: self.level,
+      kv: self
+        .kv
+        .iter()
+        .map(|(k, v)| (*k, v.to_string()))
+        .collect::<Vec<_>>()
+        .into(),
+      module: self.module.to_owned(),
+      file: self.file.to_owned(),
+      line: self.line,
+    }
+  }
+}
+
+impl From<&Log<'_>> for Record {
+  fn from(log: &Log) -> Self {
+    log.to_owned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trip() {
+    let log = Log {
+      timestamp: OffsetDateTime::UNIX_EPOCH,
+      level: LogLevel::Info,
+      kv: &[
+        ("key1", format_args!("value1")),
+        ("key1.2", format_args!("{:?}", "value1.2")),
+        ("message", format_args!("logmsg")),
+      ],
+      module: "tests",
+      file: "record.rs",
+      line: 10,
+    };
+
+    let record = std::thread::spawn({
+      let record = log.to_owned();
+      move || record
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(record.message(), "logmsg");
+    assert_eq!(record.get("key1.2"), Some("\"value1.2\""));
+
+    for format in [LogFormat::Compact, LogFormat::Json] {
+      let mut expected: Vec<u8> = vec![];
+      log.write(&mut expected, &format).unwrap();
+
+      let mut w: Vec<u8> = vec![];
+      record.write(&mut w, &format).unwrap();
+
+      assert_eq!(String::from_utf8(w), String::from_utf8(expected));
+    }
+  }
+}
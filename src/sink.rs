@@ -0,0 +1,67 @@
+use std::{
+  fmt, io,
+  net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use crate::log::Log;
+
+/// A destination for log lines, in addition to `stdout` and log files.
+///
+/// Attach sinks to a [`Logger`](crate::Logger) with [`Logger::sink`](crate::Logger::sink).
+/// A sink is handed every log line that passes the logger's minimum level,
+/// along with the line already formatted in the logger's format (including the
+/// trailing newline). Lines are formatted once, no matter how many sinks there
+/// are. Sinks are free to ignore the formatted line if their destination
+/// expects something specific.
+pub trait Sink: Send + Sync {
+  fn log(&self, log: &Log, formatted: &[u8]) -> io::Result<()>;
+
+  /// Write out anything buffered by [`Sink::log`].
+  ///
+  /// [`NonBlocking`](crate::NonBlocking) sinks call this after every batch of
+  /// lines, and when no lines have come in for a while, so sinks that send
+  /// lines in batches can send one here. Loggers only call it from
+  /// [`Logger::flush`](crate::Logger::flush), so batching sinks should also
+  /// send what they buffered when they are dropped.
+  fn flush(&self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl fmt::Debug for dyn Sink {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Sink")
+  }
+}
+
+/// Calls `connect` with every address `addr` resolves to until it succeeds,
+/// returning the last error if none does.
+pub(crate) fn connect_any<T>(
+  addr: &str,
+  mut connect: impl FnMut(SocketAddr) -> io::Result<T>,
+) -> io::Result<T> {
+  let mut last = None;
+  for addr in addr.to_socket_addrs()? {
+    match connect(addr) {
+      Ok(connection) => return Ok(connection),
+      Err(e) => last = Some(e),
+    }
+  }
+
+  Err(last.unwrap_or_else(|| {
+    io::Error::new(io::ErrorKind::NotFound, format!("Cannot resolve {addr}"))
+  }))
+}
+
+/// A UDP socket connected to `addr`, bound to any local address of the same
+/// family.
+pub(crate) fn udp_socket(addr: SocketAddr) -> io::Result<UdpSocket> {
+  let local = if addr.is_ipv4() {
+    "0.0.0.0:0"
+  } else {
+    "[::]:0"
+  };
+  let socket = UdpSocket::bind(local)?;
+  socket.connect(addr)?;
+  Ok(socket)
+}
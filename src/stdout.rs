@@ -1,18 +1,144 @@
-use crate::log::{Log, LogFormat};
-use std::io::{self, stdout, Stdout};
+use crate::{format::syslog::severity, log::Log, sink::Sink, LogLevel};
+use std::io::{self, stderr, stdout, Stderr, Stdout, Write};
 
+/// A [`Sink`] that writes to `stdout`.
+///
+/// [`Logger::stdout`](crate::Logger::stdout) is the usual way to log to
+/// `stdout`, use this directly to wrap it in another sink, like
+/// [`NonBlocking`](crate::NonBlocking). Lines written through this sink are
+/// not given [level prefixes](crate::Logger::level_prefix).
 #[derive(Debug)]
 pub struct StdoutLogger {
   stdout: Stdout,
+  /// Whether `stdout` is connected to the systemd journal.
+  pub(crate) journal: bool,
 }
 
 impl StdoutLogger {
   pub fn new() -> Self {
-    Self { stdout: stdout() }
+    Self {
+      stdout: stdout(),
+      journal: connected_to_journal(stdout()),
+    }
+  }
+}
+
+impl Default for StdoutLogger {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Sink for StdoutLogger {
+  fn log(&self, _: &Log, formatted: &[u8]) -> io::Result<()> {
+    self.stdout.lock().write_all(formatted)
+  }
+}
+
+/// A [`Sink`] that writes to `stderr`, see [`StdoutLogger`].
+#[derive(Debug)]
+pub struct StderrLogger {
+  stderr: Stderr,
+  /// Whether `stderr` is connected to the systemd journal.
+  pub(crate) journal: bool,
+}
+
+impl StderrLogger {
+  pub fn new() -> Self {
+    Self {
+      stderr: stderr(),
+      journal: connected_to_journal(stderr()),
+    }
+  }
+}
+
+impl Default for StderrLogger {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Sink for StderrLogger {
+  fn log(&self, _: &Log, formatted: &[u8]) -> io::Result<()> {
+    self.stderr.lock().write_all(formatted)
+  }
+}
+
+/// Writes the syslog priority of `level`, like `<6>` for `Info`.
+///
+/// When a service's output is connected to the journal, systemd strips these
+/// prefixes and uses them as the priority of the line (see `sd-daemon(3)`).
+pub(crate) fn write_prefix<W: Write>(
+  w: &mut W,
+  level: LogLevel,
+) -> io::Result<()> {
+  write!(w, "<{}>", severity(level))
+}
+
+/// Whether `stream` is connected to the systemd journal.
+///
+/// systemd sets `JOURNAL_STREAM` to the device and inode numbers of the stream
+/// it connects a service's output to. The variable is inherited by child
+/// processes, so the numbers have to be compared to the stream's own.
+#[cfg(unix)]
+fn connected_to_journal<S: std::os::fd::AsFd>(stream: S) -> bool {
+  use std::{fs::File, os::unix::fs::MetadataExt};
+
+  let Ok(journal) = std::env::var("JOURNAL_STREAM") else {
+    return false;
+  };
+  let Ok(metadata) = stream
+    .as_fd()
+    .try_clone_to_owned()
+    .and_then(|fd| File::from(fd).metadata())
+  else {
+    return false;
+  };
+
+  is_stream(&journal, metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn connected_to_journal<S>(_: S) -> bool {
+  false
+}
+
+/// Whether a `JOURNAL_STREAM` value of `device:inode` matches `dev` and `ino`.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn is_stream(stream: &str, dev: u64, ino: u64) -> bool {
+  match stream.split_once(':') {
+    Some((d, i)) => d.parse() == Ok(dev) && i.parse() == Ok(ino),
+    None => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn prefixes() {
+    let levels = [
+      (LogLevel::Trace, "<7>"),
+      (LogLevel::Debug, "<7>"),
+      (LogLevel::Info, "<6>"),
+      (LogLevel::Warn, "<4>"),
+      (LogLevel::Error, "<3>"),
+      (LogLevel::Fatal, "<2>"),
+    ];
+
+    for (level, prefix) in levels {
+      let mut w = vec![];
+      write_prefix(&mut w, level).unwrap();
+      assert_eq!(w, prefix.as_bytes());
+    }
   }
 
-  pub fn log(&self, log: &Log, format: &LogFormat) -> io::Result<()> {
-    let writer = &mut self.stdout.lock();
-    log.write(writer, format)
+  #[test]
+  fn journal_stream() {
+    assert!(is_stream("8:1234", 8, 1234));
+    assert!(!is_stream("8:1234", 8, 1235));
+    assert!(!is_stream("8", 8, 1234));
+    assert!(!is_stream("", 8, 1234));
   }
 }
@@ -0,0 +1,285 @@
+use std::{
+  io::{self, Write},
+  net::{TcpStream, UdpSocket},
+  time::Duration,
+};
+#[cfg(unix)]
+use std::{os::unix::net::UnixDatagram, path::PathBuf};
+
+use parking_lot::Mutex;
+
+use crate::{
+  format::{escape::with_scratch, Facility, Syslog},
+  log::Log,
+  sink::{connect_any, udp_socket, Sink},
+};
+
+#[derive(Debug)]
+enum Transport {
+  #[cfg(unix)]
+  Unix(PathBuf),
+  Udp(String),
+  Tcp(String),
+}
+
+#[derive(Debug)]
+enum Connection {
+  #[cfg(unix)]
+  Unix(UnixDatagram),
+  Udp(UdpSocket),
+  Tcp(TcpStream),
+}
+
+/// A [`Sink`] that sends log lines to a syslog daemon or collector.
+///
+/// Lines are formatted as [RFC 5424](https://datatracker.ietf.org/doc/html/rfc5424)
+/// messages no matter what format the [`Logger`](crate::Logger) uses, or as
+/// RFC 3164 messages when sent to a Unix socket (see [`SyslogSink::rfc3164`]).
+/// Messages can be sent:
+///
+/// - To the local syslog daemon over the `/dev/log` Unix datagram socket
+/// - To a collector over UDP, one message per datagram
+/// - To a collector over TCP, with octet-counting framing
+///   ([RFC 6587](https://datatracker.ietf.org/doc/html/rfc6587#section-3.4.1))
+///
+/// The connection is established when the first line is logged. If sending a
+/// line fails, the sink reconnects and tries once more before giving up on
+/// that line.
+///
+/// # Examples
+/// ```no_run
+/// use lumbermill::{format::Facility, Logger, SyslogSink};
+///
+/// let syslog = SyslogSink::tcp("logs.internal:601").facility(Facility::Local0);
+/// Logger::default().stdout(false).sink(syslog).init();
+/// ```
+#[derive(Debug)]
+pub struct SyslogSink {
+  transport: Transport,
+  syslog: Syslog,
+  rfc3164: bool,
+  timeout: Duration,
+  connection: Mutex<Option<Connection>>,
+}
+
+impl SyslogSink {
+  /// Send to the local syslog daemon over `/dev/log`.
+  #[cfg(unix)]
+  pub fn unix() -> Self {
+    Self::unix_path("/dev/log")
+  }
+
+  /// Send to a Unix datagram socket at `path`. Messages are RFC 3164 by
+  /// default, which is what local daemons (and glibc's `syslog()`) expect.
+  #[cfg(unix)]
+  pub fn unix_path<P: Into<PathBuf>>(path: P) -> Self {
+    Self {
+      rfc3164: true,
+      ..Self::new(Transport::Unix(path.into()))
+    }
+  }
+
+  /// Send to a collector at `addr` (like `"logs.internal:514"`) over UDP. The
+  /// address is resolved again every time the sink reconnects, and every
+  /// address it resolves to is tried in turn.
+  pub fn udp(addr: &str) -> Self {
+    Self::new(Transport::Udp(addr.to_owned()))
+  }
+
+  /// Send to a collector at `addr` (like `"logs.internal:601"`) over TCP. The
+  /// address is resolved again every time the sink reconnects, and every
+  /// address it resolves to is tried in turn.
+  pub fn tcp(addr: &str) -> Self {
+    Self::new(Transport::Tcp(addr.to_owned()))
+  }
+
+  fn new(transport: Transport) -> Self {
+    Self {
+      transport,
+      syslog: Syslog::default(),
+      rfc3164: false,
+      timeout: Duration::from_secs(5),
+      connection: Mutex::new(None),
+    }
+  }
+
+  /// Header fields of messages, like the hostname and application name.
+  pub fn syslog(mut self, syslog: Syslog) -> Self {
+    self.syslog = syslog;
+    self
+  }
+
+  pub fn facility(mut self, facility: Facility) -> Self {
+    self.syslog = self.syslog.facility(facility);
+    self
+  }
+
+  /// Send legacy RFC 3164 messages instead of RFC 5424 ones. Defaults to
+  /// `true` for Unix sockets and `false` for UDP and TCP.
+  pub fn rfc3164(mut self, rfc3164: bool) -> Self {
+    self.rfc3164 = rfc3164;
+    self
+  }
+
+  /// How long connecting and sending over TCP may take. Defaults to 5 seconds.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  fn connect(&self) -> io::Result<Connection> {
+    match &self.transport {
+      #[cfg(unix)]
+      Transport::Unix(path) => {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Connection::Unix(socket))
+      }
+      Transport::Udp(addr) => {
+        Ok(Connection::Udp(connect_any(addr, udp_socket)?))
+      }
+      Transport::Tcp(addr) => {
+        let stream = connect_any(addr, |addr| {
+          TcpStream::connect_timeout(&addr, self.timeout)
+        })?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+        Ok(Connection::Tcp(stream))
+      }
+    }
+  }
+}
+
+impl Connection {
+  fn send(&mut self, message: &[u8]) -> io::Result<()> {
+    match self {
+      #[cfg(unix)]
+      Self::Unix(socket) => socket.send(message).map(|_| ()),
+      Self::Udp(socket) => socket.send(message).map(|_| ()),
+      Self::Tcp(stream) => {
+        // Octet counting: the length of the message, a space, then the message
+        write!(stream, "{} ", message.len())?;
+        stream.write_all(message)
+      }
+    }
+  }
+}
+
+impl Sink for SyslogSink {
+  fn log(&self, log: &Log, _: &[u8]) -> io::Result<()> {
+    with_scratch(|message| {
+      if self.rfc3164 {
+        self.syslog.write_3164(log, message)?;
+      } else {
+        self.syslog.write_5424(log, message)?;
+      }
+
+      // Messages are framed by the transport, they do not need a newline
+      if message.last() == Some(&b'\n') {
+        message.pop();
+      }
+
+      let mut connection = self.connection.lock();
+      if let Some(c) = connection.as_mut() {
+        if c.send(message).is_ok() {
+          return Ok(());
+        }
+      }
+
+      // Not connected yet, or the connection broke
+      *connection = None;
+      let mut c = self.connect()?;
+      c.send(message)?;
+      *connection = Some(c);
+
+      Ok(())
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{io::Read, net::TcpListener};
+
+  use super::*;
+  use crate::{format::Syslog, info, warn, Logger};
+
+  fn syslog() -> Syslog {
+    Syslog::new().hostname("fra1").app_name("tests").procid("1")
+  }
+
+  #[test]
+  fn udp() {
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = collector.local_addr().unwrap().to_string();
+    let logger = Logger::new()
+      .stdout(false)
+      .sink(SyslogSink::udp(&addr).syslog(syslog()).rfc3164(true));
+
+    warn!(logger: &logger, count = 9001, "Too many items in queue");
+
+    let mut buf = [0; 1024];
+    let len = collector.recv(&mut buf).unwrap();
+    let message = std::str::from_utf8(&buf[..len]).unwrap();
+    assert!(message.starts_with("<12>"));
+    assert!(
+      message.ends_with(" fra1 tests[1]: Too many items in queue count=9001")
+    );
+  }
+
+  #[test]
+  fn tcp() {
+    let collector = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = collector.local_addr().unwrap().to_string();
+    let logger = Logger::new().stdout(false).sink(
+      SyslogSink::tcp(&addr)
+        .syslog(syslog())
+        .facility(Facility::Local0),
+    );
+
+    info!(logger: &logger, "First");
+    info!(logger: &logger, "Second");
+    drop(logger);
+
+    let mut received = String::new();
+    let (mut stream, _) = collector.accept().unwrap();
+    stream.read_to_string(&mut received).unwrap();
+
+    let mut frames = vec![];
+    let mut rest = received.as_str();
+    while let Some((len, tail)) = rest.split_once(' ') {
+      let len: usize = len.parse().unwrap();
+      frames.push(&tail[..len]);
+      rest = &tail[len..];
+    }
+
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].starts_with("<134>1 "));
+    assert!(frames[0].ends_with(" fra1 tests 1 - - First"));
+    assert!(frames[1].ends_with(" fra1 tests 1 - - Second"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn reconnects() {
+    let path = std::env::temp_dir()
+      .join(format!("lumbermill-syslog-{}.sock", std::process::id()));
+    _ = std::fs::remove_file(&path);
+
+    let sink = SyslogSink::unix_path(&path).syslog(syslog());
+    let logger = Logger::new().stdout(false).sink(sink);
+
+    // Nobody is listening yet, so this line is lost
+    info!(logger: &logger, "Lost");
+
+    let daemon = UnixDatagram::bind(&path).unwrap();
+    info!(logger: &logger, "Delivered");
+
+    let mut buf = [0; 1024];
+    let len = daemon.recv(&mut buf).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let message = std::str::from_utf8(&buf[..len]).unwrap();
+    assert!(message.ends_with(" tests[1]: Delivered"));
+  }
+}
@@ -0,0 +1,224 @@
+//! Helpers to assert on log lines in tests.
+//!
+//! The easiest way in is [`capture`], which sends every log line emitted on the
+//! current thread to an in-memory [`CaptureSink`] while a closure runs. Inside
+//! it, [`assert_logged!`](crate::assert_logged) checks that a matching line was
+//! logged.
+//!
+//! ```
+//! use lumbermill::{assert_logged, testing, warn, LogLevel};
+//!
+//! testing::capture(|| {
+//!   warn!(count = 9001, "Too many items in queue");
+//!
+//!   assert_logged!(LogLevel::Warn, message contains "queue", count = "9001");
+//! });
+//! ```
+
+use std::{cell::RefCell, fmt, io, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::{with_logger, Log, LogLevel, Logger, Record, Sink};
+
+/// A [`Sink`] that keeps log lines in memory.
+///
+/// Clones share the same storage, so keep a clone around to inspect what was
+/// logged after handing the sink to a [`Logger`].
+///
+/// # Examples
+/// ```
+/// use lumbermill::{info, testing::CaptureSink, Logger};
+///
+/// let capture = CaptureSink::new();
+/// let logger = Logger::new().stdout(false).sink(capture.clone());
+///
+/// info!(logger: &logger, "Listening");
+/// assert_eq!(capture.logs()[0].message(), "Listening");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CaptureSink {
+  logs: Arc<Mutex<Vec<Record>>>,
+  output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureSink {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Log lines captured so far.
+  pub fn logs(&self) -> Vec<Record> {
+    self.logs.lock().clone()
+  }
+
+  /// Log lines captured so far, formatted by the logger they were sent to.
+  pub fn output(&self) -> String {
+    String::from_utf8_lossy(&self.output.lock()).into_owned()
+  }
+
+  pub fn clear(&self) {
+    self.logs.lock().clear();
+    self.output.lock().clear();
+  }
+}
+
+impl Sink for CaptureSink {
+  fn log(&self, log: &Log, formatted: &[u8]) -> io::Result<()> {
+    self.logs.lock().push(log.to_owned());
+    self.output.lock().extend_from_slice(formatted);
+
+    Ok(())
+  }
+}
+
+thread_local! {
+  static CURRENT: RefCell<Option<CaptureSink>> = const { RefCell::new(None) };
+}
+
+/// Capture every log line emitted on the current thread (at any level) while
+/// `f` runs.
+///
+/// Captured lines are what [`assert_logged!`](crate::assert_logged) checks
+/// against when it is not given a sink explicitly. Use [`current`] to inspect
+/// them directly.
+pub fn capture<R>(f: impl FnOnce() -> R) -> R {
+  struct Restore(Option<CaptureSink>);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      CURRENT.with(|c| c.replace(self.0.take()));
+    }
+  }
+
+  let sink = CaptureSink::new();
+  let logger = Logger::new()
+    .level(LogLevel::Trace)
+    .stdout(false)
+    .sink(sink.clone());
+
+  let _restore = Restore(CURRENT.with(|c| c.replace(Some(sink))));
+  with_logger(&logger, f)
+}
+
+/// The sink of the innermost [`capture`] on the current thread.
+///
+/// # Panics
+///
+/// When called outside of [`capture`].
+pub fn current() -> CaptureSink {
+  CURRENT
+    .with(|c| c.borrow().clone())
+    .expect("Must be called inside `lumbermill::testing::capture`")
+}
+
+/// Internal-only, built by [`assert_logged!`](crate::assert_logged).
+#[doc(hidden)]
+#[derive(Debug)]
+pub enum Matcher {
+  Equals(&'static str, String),
+  Contains(&'static str, String),
+}
+
+impl Matcher {
+  fn matches(&self, log: &Record) -> bool {
+    match self {
+      Self::Equals(k, v) => log.get(k) == Some(v.as_str()),
+      Self::Contains(k, v) => {
+        log.get(k).is_some_and(|l| l.contains(v.as_str()))
+      }
+    }
+  }
+}
+
+impl fmt::Display for Matcher {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Equals(k, v) => write!(f, "{k} = {v:?}"),
+      Self::Contains(k, v) => write!(f, "{k} contains {v:?}"),
+    }
+  }
+}
+
+/// Internal-only, do not use directly. Use [`assert_logged!`](crate::assert_logged).
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_logged(
+  sink: &CaptureSink,
+  level: LogLevel,
+  matchers: &[Matcher],
+) {
+  let logs = sink.logs();
+  let found = logs
+    .iter()
+    .any(|log| log.level == level && matchers.iter().all(|m| m.matches(log)));
+
+  if !found {
+    let expected = matchers
+      .iter()
+      .map(Matcher::to_string)
+      .collect::<Vec<_>>()
+      .join(", ");
+    let captured = logs
+      .iter()
+      .map(|log| {
+        let kv = log.kv.iter().map(|(k, v)| format!(" {k}={v:?}"));
+        format!("  level={:?}{}\n", log.level, kv.collect::<String>())
+      })
+      .collect::<String>();
+
+    panic!(
+      "No `{level:?}` log line matched [{expected}]\nCaptured {} log line(s):\n{captured}",
+      logs.len()
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{assert_logged, error, info, warn};
+
+  #[test]
+  fn captures() {
+    let logs = capture(|| {
+      info!("Listening");
+      warn!(count = 9001, "Too many items in queue");
+
+      assert_logged!(LogLevel::Info);
+      assert_logged!(LogLevel::Warn, message contains "queue", count = "9001");
+      assert_logged!(
+        LogLevel::Warn,
+        count = 9001,
+        message = "Too many items in queue",
+      );
+
+      current().logs()
+    });
+
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[1].module, module_path!());
+  }
+
+  #[test]
+  fn explicit_sink() {
+    let sink = CaptureSink::new();
+    let logger = Logger::new().stdout(false).compact().sink(sink.clone());
+
+    error!(logger: &logger, reason = "No connectivity", "Connection dropped");
+
+    assert_logged!(sink: sink, LogLevel::Error, reason = "No connectivity");
+    assert!(sink.output().contains(
+      "level=error message=\"Connection dropped\" reason=No connectivity"
+    ));
+  }
+
+  #[test]
+  #[should_panic(expected = "No `warn` log line matched [count = \"9000\"]")]
+  fn mismatch() {
+    capture(|| {
+      warn!(count = 9001, "Too many items in queue");
+      assert_logged!(LogLevel::Warn, count = "9000");
+    });
+  }
+}
@@ -0,0 +1,257 @@
+use std::{
+  cell::Cell,
+  io::{self, Write},
+};
+
+use time::{format_description::BorrowedFormatItem, OffsetDateTime, UtcOffset};
+
+/// How log lines render their timestamp. Set it with
+/// [`Logger::timestamp`](crate::Logger::timestamp).
+///
+/// # Examples
+/// ```
+/// use lumbermill::{Logger, Precision, TimestampFormat};
+/// use time::macros::format_description;
+///
+/// // Integer microseconds since the epoch, like `1686735665123456`
+/// Logger::default().timestamp(TimestampFormat::Unix(Precision::Micros));
+///
+/// // Anything `time` can format
+/// Logger::default().timestamp(TimestampFormat::Custom(format_description!(
+///   "[day]/[month]/[year] [hour]:[minute]:[second]"
+/// )));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+  /// RFC 3339, like `2023-06-14T09:41:05.123Z`. This is the default, with
+  /// millisecond precision. The `Pretty` format only prints the time of day.
+  Rfc3339(Precision),
+  /// Time since the Unix epoch, in seconds, milliseconds, microseconds or
+  /// nanoseconds, like `1686735665123`.
+  Unix(Precision),
+  /// A custom format, usually created with `time`'s `format_description!`
+  /// macro.
+  Custom(&'static [BorrowedFormatItem<'static>]),
+  /// No timestamp at all, for destinations that stamp lines themselves (like
+  /// systemd's journal).
+  None,
+}
+
+/// The smallest unit of time a [`TimestampFormat`] renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+  Seconds,
+  Millis,
+  Micros,
+  Nanos,
+}
+
+impl Default for TimestampFormat {
+  fn default() -> Self {
+    Self::Rfc3339(Precision::Millis)
+  }
+}
+
+impl TimestampFormat {
+  /// Whether this format renders a number, which some formats (like JSON) do
+  /// not quote.
+  pub(crate) fn is_numeric(&self) -> bool {
+    matches!(self, Self::Unix(_))
+  }
+
+  /// Write `t` in this format. With `time_only`, RFC 3339 timestamps leave out
+  /// the date.
+  pub(crate) fn write<W: io::Write + ?Sized>(
+    &self,
+    w: &mut W,
+    t: &OffsetDateTime,
+    time_only: bool,
+  ) -> io::Result<()> {
+    match self {
+      Self::Rfc3339(precision) => {
+        with_seconds(t, |s| {
+          if time_only {
+            w.write_all(&s.as_bytes()[s.len() - 8..])
+          } else {
+            w.write_all(s.as_bytes())
+          }
+        })?;
+
+        match precision {
+          Precision::Seconds => {}
+          Precision::Millis => write!(w, ".{:0>3}", t.millisecond())?,
+          Precision::Micros => write!(w, ".{:0>6}", t.microsecond())?,
+          Precision::Nanos => write!(w, ".{:0>9}", t.nanosecond())?,
+        }
+
+        let offset = t.offset();
+        if offset.is_utc() {
+          w.write_all(b"Z")
+        } else {
+          let sign = if offset.is_negative() { '-' } else { '+' };
+          write!(
+            w,
+            "{sign}{:0>2}:{:0>2}",
+            offset.whole_hours().unsigned_abs(),
+            offset.minutes_past_hour().unsigned_abs()
+          )
+        }
+      }
+      Self::Unix(precision) => {
+        let nanos = t.unix_timestamp_nanos();
+        let divisor = match precision {
+          Precision::Seconds => 1_000_000_000,
+          Precision::Millis => 1_000_000,
+          Precision::Micros => 1_000,
+          Precision::Nanos => 1,
+        };
+        write!(w, "{}", nanos.div_euclid(divisor))
+      }
+      Self::Custom(items) => t
+        .format_into(w, items)
+        .map(|_| ())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+      Self::None => Ok(()),
+    }
+  }
+}
+
+/// A rendered `YYYY-MM-DDTHH:MM:SS` prefix, and the second it was rendered for.
+#[derive(Clone, Copy)]
+struct Seconds {
+  key: Option<(i64, UtcOffset)>,
+  buf: [u8; 32],
+  len: usize,
+}
+
+impl Seconds {
+  const EMPTY: Self = Self {
+    key: None,
+    buf: [0; 32],
+    len: 0,
+  };
+
+  fn render(t: &OffsetDateTime) -> Self {
+    let mut buf = [0; 32];
+    let len = {
+      let mut w = &mut buf[..];
+      // Dates take at most 13 characters (`-999999-12-31`), so this always fits
+      _ = write!(
+        w,
+        "{}T{:0>2}:{:0>2}:{:0>2}",
+        t.date(),
+        t.hour(),
+        t.minute(),
+        t.second()
+      );
+      32 - w.len()
+    };
+
+    Self {
+      key: Some((t.unix_timestamp(), t.offset())),
+      buf,
+      len,
+    }
+  }
+
+  fn as_str(&self) -> &str {
+    std::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+  }
+}
+
+thread_local! {
+  static CACHE: Cell<Seconds> = const { Cell::new(Seconds::EMPTY) };
+}
+
+/// Calls `f` with `t` rendered as `YYYY-MM-DDTHH:MM:SS`.
+///
+/// The last rendering is cached per thread, so lines logged within the same
+/// second only need to render their sub-second part.
+pub(crate) fn with_seconds<R>(
+  t: &OffsetDateTime,
+  f: impl FnOnce(&str) -> R,
+) -> R {
+  let key = (t.unix_timestamp(), t.offset());
+  let mut seconds = CACHE.try_with(Cell::get).unwrap_or(Seconds::EMPTY);
+  if seconds.key != Some(key) {
+    seconds = Seconds::render(t);
+    _ = CACHE.try_with(|cache| cache.set(seconds));
+  }
+
+  f(seconds.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+  use time::{Duration, UtcOffset};
+
+  use super::*;
+
+  fn render(t: OffsetDateTime) -> String {
+    with_seconds(&t, str::to_owned)
+  }
+
+  fn format(t: OffsetDateTime, format: TimestampFormat) -> String {
+    let mut w = vec![];
+    format.write(&mut w, &t, false).unwrap();
+    String::from_utf8(w).unwrap()
+  }
+
+  #[test]
+  fn rerenders_when_the_second_changes() {
+    let t = OffsetDateTime::UNIX_EPOCH;
+
+    assert_eq!(render(t), "1970-01-01T00:00:00");
+    assert_eq!(
+      render(t + Duration::milliseconds(999)),
+      "1970-01-01T00:00:00"
+    );
+    assert_eq!(render(t + Duration::seconds(1)), "1970-01-01T00:00:01");
+    assert_eq!(
+      render(t + Duration::days(365 * 30 + 7)),
+      "2000-01-01T00:00:00"
+    );
+    assert_eq!(
+      render(t.to_offset(UtcOffset::from_hms(5, 30, 0).unwrap())),
+      "1970-01-01T05:30:00"
+    );
+    assert_eq!(render(t), "1970-01-01T00:00:00");
+  }
+
+  #[test]
+  fn formats() {
+    let t =
+      OffsetDateTime::from_unix_timestamp_nanos(1_686_735_665_123_456_789)
+        .unwrap();
+
+    let rfc3339 = |p| format(t, TimestampFormat::Rfc3339(p));
+    assert_eq!(rfc3339(Precision::Seconds), "2023-06-14T09:41:05Z");
+    assert_eq!(rfc3339(Precision::Millis), "2023-06-14T09:41:05.123Z");
+    assert_eq!(rfc3339(Precision::Micros), "2023-06-14T09:41:05.123456Z");
+    assert_eq!(rfc3339(Precision::Nanos), "2023-06-14T09:41:05.123456789Z");
+    assert_eq!(
+      format(
+        t.to_offset(UtcOffset::from_hms(-3, -30, 0).unwrap()),
+        TimestampFormat::default()
+      ),
+      "2023-06-14T06:11:05.123-03:30"
+    );
+
+    let unix = |p| format(t, TimestampFormat::Unix(p));
+    assert_eq!(unix(Precision::Seconds), "1686735665");
+    assert_eq!(unix(Precision::Millis), "1686735665123");
+    assert_eq!(unix(Precision::Micros), "1686735665123456");
+    assert_eq!(unix(Precision::Nanos), "1686735665123456789");
+
+    assert_eq!(
+      format(
+        t,
+        TimestampFormat::Custom(time::macros::format_description!(
+          "[day]/[month]/[year]"
+        ))
+      ),
+      "14/06/2023"
+    );
+    assert_eq!(format(t, TimestampFormat::None), "");
+  }
+}